@@ -1,4 +1,4 @@
-import falcon, json, logging, datetime
+import falcon, json, logging, datetime, os
 
 logging.basicConfig()
 logging.getLogger().setLevel(logging.DEBUG)
@@ -15,6 +15,10 @@ class CookiesSetter(object):
 
 class WindguruResource:
     def __init__(self):
+        # responds with 503 to every n-th request to exercise retries, disabled when 0
+        self.fail_every = int(os.environ.get('MOCK_FAIL_EVERY', '0'))
+        self.requests_count = 0
+
         with open('meta_spot.json', 'r') as f:
             self.response_body_meta = json.load(f)
 
@@ -25,6 +29,12 @@ class WindguruResource:
             self.response_body_station_data = json.load(f)
 
     def on_get(self, req, resp):
+        self.requests_count += 1
+        if self.fail_every and self.requests_count % self.fail_every == 0:
+            logging.info("failing request on purpose")
+            resp.status = falcon.HTTP_503
+            return
+
         for cookie_name, cookie_val in CookiesSetter.cookies.items():
             if req.get_cookie_values(cookie_name)[0] != cookie_val or req.get_header('Referer') != 'https://www.windguru.cz':
                 resp.status = falcon.HTTP_403
@@ -0,0 +1,3 @@
+pub mod migrations;
+pub mod stations;
+pub mod watch_list;
@@ -0,0 +1,290 @@
+use std::{collections::HashMap, fmt, path::Path};
+
+use sqlx::{
+    migrate::{AppliedMigration, Migrate, MigrateError, Migration, Migrator},
+    PgPool,
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum MigrationError {
+    #[error("{0}")]
+    Migrate(#[from] MigrateError),
+
+    #[error("database error err={0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("version {0} is not a migration of the directory")]
+    UnknownVersion(i64),
+
+    #[error("migration {0} has no down migration, so it can not be reverted")]
+    Irreversible(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// Applied, but edited since then
+    Changed,
+    /// Failed while being applied
+    Dirty,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+    pub reversible: bool,
+}
+
+impl fmt::Display for MigrationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = format!("{:?}", self.state).to_lowercase();
+        write!(f, "{} {state:<8} {}", self.version, self.description)?;
+        if !self.reversible {
+            write!(f, " (irreversible)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Migrations found in a directory. Services sharing a database record their migrations
+/// in the same table, so those of the other services are ignored.
+pub struct Migrations {
+    migrator: Migrator,
+}
+
+impl Migrations {
+    pub async fn new(migrations_path: &Path) -> Result<Self, MigrationError> {
+        let mut migrator = Migrator::new(migrations_path).await?;
+        migrator.set_ignore_missing(true);
+
+        Ok(Self { migrator })
+    }
+
+    pub async fn status(&self, pool: &PgPool) -> Result<Vec<MigrationStatus>, MigrationError> {
+        let (applied, dirty) = applied_migrations(pool).await?;
+
+        Ok(migration_status(&self.migrator.migrations, &applied, dirty))
+    }
+
+    /// Migrations which `run` would apply, in order
+    pub async fn pending(&self, pool: &PgPool) -> Result<Vec<&Migration>, MigrationError> {
+        let status = self.status(pool).await?;
+        check_applied(&status)?;
+
+        let pending = status
+            .iter()
+            .filter(|status| status.state == MigrationState::Pending)
+            .map(|status| status.version)
+            .collect::<Vec<_>>();
+
+        Ok(self
+            .migrator
+            .iter()
+            .filter(|m| !m.migration_type.is_down_migration() && pending.contains(&m.version))
+            .collect())
+    }
+
+    pub async fn run(&self, pool: &PgPool) -> Result<(), MigrationError> {
+        Ok(self.migrator.run(pool).await?)
+    }
+
+    /// Down migrations which `revert` would apply to get back to `target`, in order
+    pub async fn reverted(
+        &self,
+        pool: &PgPool,
+        target: i64,
+    ) -> Result<Vec<&Migration>, MigrationError> {
+        let status = self.status(pool).await?;
+        check_applied(&status)?;
+
+        reverted_migrations(&self.migrator.migrations, &status, target)
+    }
+
+    /// Reverts applied migrations newer than `target`, 0 reverts all of them
+    pub async fn revert(&self, pool: &PgPool, target: i64) -> Result<(), MigrationError> {
+        self.reverted(pool, target).await?;
+
+        Ok(self.migrator.undo(pool, target).await?)
+    }
+}
+
+pub async fn run_migrations(pool: &PgPool, migrations_path: &Path) -> Result<(), MigrationError> {
+    Migrations::new(migrations_path).await?.run(pool).await
+}
+
+/// SQL of the migrations, each preceded by a comment with its version
+pub fn migrations_script(migrations: &[&Migration]) -> String {
+    migrations
+        .iter()
+        .map(|m| format!("-- {} {}\n{}\n", m.version, m.description, m.sql.trim_end()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+async fn applied_migrations(
+    pool: &PgPool,
+) -> Result<(HashMap<i64, AppliedMigration>, Option<i64>), MigrationError> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+
+    let dirty = conn.dirty_version().await?;
+    let applied = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|m| (m.version, m))
+        .collect();
+
+    Ok((applied, dirty))
+}
+
+fn migration_status(
+    migrations: &[Migration],
+    applied: &HashMap<i64, AppliedMigration>,
+    dirty: Option<i64>,
+) -> Vec<MigrationStatus> {
+    migrations
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| {
+            let state = match applied.get(&m.version) {
+                _ if dirty == Some(m.version) => MigrationState::Dirty,
+                Some(applied) if applied.checksum != m.checksum => MigrationState::Changed,
+                Some(_) => MigrationState::Applied,
+                None => MigrationState::Pending,
+            };
+
+            MigrationStatus {
+                version: m.version,
+                description: m.description.to_string(),
+                state,
+                reversible: m.migration_type.is_reversible(),
+            }
+        })
+        .collect()
+}
+
+/// Fails the same way running the migrations would
+fn check_applied(status: &[MigrationStatus]) -> Result<(), MigrateError> {
+    for status in status {
+        match status.state {
+            MigrationState::Dirty => return Err(MigrateError::Dirty(status.version)),
+            MigrationState::Changed => return Err(MigrateError::VersionMismatch(status.version)),
+            MigrationState::Applied | MigrationState::Pending => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn reverted_migrations<'m>(
+    migrations: &'m [Migration],
+    status: &[MigrationStatus],
+    target: i64,
+) -> Result<Vec<&'m Migration>, MigrationError> {
+    if target != 0 && !status.iter().any(|status| status.version == target) {
+        return Err(MigrationError::UnknownVersion(target));
+    }
+
+    status
+        .iter()
+        .rev()
+        .filter(|status| status.state == MigrationState::Applied && status.version > target)
+        .map(|status| {
+            migrations
+                .iter()
+                .find(|m| m.version == status.version && m.migration_type.is_down_migration())
+                .ok_or(MigrationError::Irreversible(status.version))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use pretty_assertions::assert_eq;
+    use sqlx::migrate::MigrationType;
+
+    use super::*;
+
+    fn migration(version: i64, migration_type: MigrationType, sql: &'static str) -> Migration {
+        Migration::new(
+            version,
+            Cow::Borrowed("spots"),
+            migration_type,
+            Cow::Borrowed(sql),
+        )
+    }
+
+    #[test]
+    fn revert_applied_migrations_down_to_target() {
+        let migrations = vec![
+            migration(1, MigrationType::ReversibleUp, "CREATE TABLE a ();"),
+            migration(1, MigrationType::ReversibleDown, "DROP TABLE a;"),
+            migration(2, MigrationType::ReversibleUp, "CREATE TABLE b ();"),
+            migration(2, MigrationType::ReversibleDown, "DROP TABLE b;"),
+            migration(3, MigrationType::Simple, "CREATE TABLE c ();"),
+        ];
+        let applied = |versions: &[i64]| {
+            versions
+                .iter()
+                .map(|version| {
+                    let checksum = migrations
+                        .iter()
+                        .find(|m| m.version == *version)
+                        .unwrap()
+                        .checksum
+                        .clone();
+                    (
+                        *version,
+                        AppliedMigration {
+                            version: *version,
+                            checksum,
+                        },
+                    )
+                })
+                .collect::<HashMap<_, _>>()
+        };
+
+        let status = migration_status(&migrations, &applied(&[1, 2]), None);
+        assert_eq!(
+            status.iter().map(|s| s.state).collect::<Vec<_>>(),
+            vec![
+                MigrationState::Applied,
+                MigrationState::Applied,
+                MigrationState::Pending
+            ]
+        );
+
+        let reverted = reverted_migrations(&migrations, &status, 0).unwrap();
+        assert_eq!(
+            migrations_script(&reverted),
+            "-- 2 spots\nDROP TABLE b;\n\n-- 1 spots\nDROP TABLE a;\n"
+        );
+        assert_eq!(
+            reverted_migrations(&migrations, &status, 1).unwrap().len(),
+            1
+        );
+        assert!(matches!(
+            reverted_migrations(&migrations, &status, 4),
+            Err(MigrationError::UnknownVersion(4))
+        ));
+
+        let status = migration_status(&migrations, &applied(&[1, 2, 3]), None);
+        assert!(matches!(
+            reverted_migrations(&migrations, &status, 1),
+            Err(MigrationError::Irreversible(3))
+        ));
+
+        let mut edited = applied(&[1]);
+        edited.get_mut(&1).unwrap().checksum = Cow::Borrowed(&[0]);
+        let status = migration_status(&migrations, &edited, None);
+        assert_eq!(status[0].state, MigrationState::Changed);
+        assert!(check_applied(&status).is_err());
+    }
+}
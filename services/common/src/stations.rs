@@ -0,0 +1,4 @@
+/// Averaging window of station readings, in minutes, when a station does not set one
+pub fn default_avg_minutes() -> u32 {
+    5
+}
@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::stations::default_avg_minutes;
+
+pub type IdSpot = i32;
+pub type IdModel = i32;
+pub type IdStation = i32;
+
+/// Spot the ingester fetches forecasts for, together with stations observing it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WatchedSpot {
+    pub id_spot: IdSpot,
+    /// Models to fetch, every model offered for the spot when empty
+    #[serde(default)]
+    pub models: Vec<IdModel>,
+    #[serde(default)]
+    pub stations: Vec<WatchedStation>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WatchedStation {
+    pub id_station: IdStation,
+    #[serde(default = "default_avg_minutes")]
+    pub avg_minutes: u32,
+}
+
+/// Every watched spot with its stations, ordered by their ids
+pub async fn list_watched_spots(pool: &PgPool) -> Result<Vec<WatchedSpot>, sqlx::Error> {
+    let spots: Vec<(IdSpot, Vec<IdModel>)> =
+        sqlx::query_as("SELECT id_spot, models FROM watched_spots ORDER BY id_spot")
+            .fetch_all(pool)
+            .await?;
+    let stations: Vec<(IdSpot, IdStation, i32)> = sqlx::query_as(
+        "SELECT id_spot, id_station, avg_minutes FROM watched_spot_stations ORDER BY id_spot, id_station",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut stations_by_spot: BTreeMap<IdSpot, Vec<WatchedStation>> = BTreeMap::new();
+    for (id_spot, id_station, avg_minutes) in stations {
+        stations_by_spot
+            .entry(id_spot)
+            .or_default()
+            .push(WatchedStation {
+                id_station,
+                avg_minutes: avg_minutes as u32,
+            });
+    }
+
+    Ok(spots
+        .into_iter()
+        .map(|(id_spot, models)| WatchedSpot {
+            id_spot,
+            models,
+            stations: stations_by_spot.remove(&id_spot).unwrap_or_default(),
+        })
+        .collect())
+}
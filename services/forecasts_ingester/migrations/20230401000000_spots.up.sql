@@ -0,0 +1,9 @@
+-- forecasts and readings reference spots, so they are created first, further columns
+-- are added along with spot metadata
+CREATE TABLE IF NOT EXISTS spots (
+  id INT PRIMARY KEY,
+  name VARCHAR(255) NOT NULL,
+  country VARCHAR(255) NOT NULL,
+  gmt_hour_offset INT NOT NULL,
+  models INTEGER[]
+);
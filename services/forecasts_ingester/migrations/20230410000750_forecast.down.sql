@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS forecasts;
+DROP TABLE IF EXISTS station_readings;
+DROP TABLE IF EXISTS models;
@@ -0,0 +1,7 @@
+-- readings are keyed by stations, which are not spots, so the foreign key to spots is
+-- not restored
+ALTER TABLE station_readings DROP CONSTRAINT IF EXISTS fk_station;
+ALTER TABLE station_readings RENAME COLUMN id_station TO id_spot;
+
+DROP TABLE IF EXISTS spot_stations;
+DROP TABLE IF EXISTS stations;
@@ -0,0 +1,23 @@
+CREATE TABLE IF NOT EXISTS stations (
+  id INT PRIMARY KEY
+);
+
+-- stations observing conditions at a spot, used to join forecasts with observations
+CREATE TABLE IF NOT EXISTS spot_stations (
+  id_spot INT NOT NULL,
+  id_station INT NOT NULL,
+  avg_minutes INT NOT NULL,
+  PRIMARY KEY (id_spot, id_station),
+  CONSTRAINT fk_station
+    FOREIGN KEY (id_station)
+      REFERENCES stations (id)
+);
+
+-- readings were always keyed by the station they come from
+ALTER TABLE station_readings RENAME COLUMN id_spot TO id_station;
+ALTER TABLE station_readings DROP CONSTRAINT IF EXISTS station_readings_id_spot_fkey;
+INSERT INTO stations (id) SELECT DISTINCT id_station FROM station_readings ON CONFLICT DO NOTHING;
+ALTER TABLE station_readings
+  ADD CONSTRAINT fk_station
+    FOREIGN KEY (id_station)
+      REFERENCES stations (id);
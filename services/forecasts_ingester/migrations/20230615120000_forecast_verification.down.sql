@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS forecast_verification;
+
+ALTER TABLE models DROP COLUMN IF EXISTS hr_step;
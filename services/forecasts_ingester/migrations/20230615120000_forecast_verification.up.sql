@@ -0,0 +1,27 @@
+-- length of a forecast step, observations are averaged over it during verification
+ALTER TABLE models ADD COLUMN IF NOT EXISTS hr_step INT;
+
+CREATE TABLE IF NOT EXISTS forecast_verification (
+  id_spot INT NOT NULL,
+  id_model INT NOT NULL,
+  id_station INT NOT NULL,
+  lead_hours INT NOT NULL,
+  period_from TIMESTAMP NOT NULL,
+  period_to TIMESTAMP NOT NULL,
+  samples INT NOT NULL,
+  wind_speed_mae REAL,
+  wind_speed_rmse REAL,
+  wind_speed_bias REAL,
+  gust_mae REAL,
+  gust_rmse REAL,
+  gust_bias REAL,
+  wind_direction_mae REAL,
+  computed_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (id_spot, id_model, id_station, lead_hours, period_from, period_to),
+  CONSTRAINT fk_model
+    FOREIGN KEY (id_model)
+      REFERENCES models (id),
+  CONSTRAINT fk_station
+    FOREIGN KEY (id_station)
+      REFERENCES stations (id)
+);
@@ -0,0 +1,7 @@
+DROP TABLE IF EXISTS spot_changes;
+
+ALTER TABLE spots DROP COLUMN IF EXISTS lat;
+ALTER TABLE spots DROP COLUMN IF EXISTS lon;
+ALTER TABLE spots DROP COLUMN IF EXISTS alt;
+ALTER TABLE spots DROP COLUMN IF EXISTS tzid;
+ALTER TABLE spots DROP COLUMN IF EXISTS updated_at;
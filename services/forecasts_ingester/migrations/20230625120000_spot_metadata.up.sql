@@ -0,0 +1,24 @@
+ALTER TABLE spots ADD COLUMN IF NOT EXISTS lat DOUBLE PRECISION;
+ALTER TABLE spots ADD COLUMN IF NOT EXISTS lon DOUBLE PRECISION;
+ALTER TABLE spots ADD COLUMN IF NOT EXISTS alt INT;
+ALTER TABLE spots ADD COLUMN IF NOT EXISTS tzid VARCHAR(255);
+ALTER TABLE spots ADD COLUMN IF NOT EXISTS updated_at TIMESTAMP NOT NULL DEFAULT NOW();
+
+-- previous versions of spot metadata, each valid until it was replaced
+CREATE TABLE IF NOT EXISTS spot_changes (
+  id_spot INT NOT NULL,
+  name VARCHAR(255) NOT NULL,
+  country VARCHAR(255) NOT NULL,
+  lat DOUBLE PRECISION,
+  lon DOUBLE PRECISION,
+  alt INT,
+  tzid VARCHAR(255),
+  gmt_hour_offset INT NOT NULL,
+  models INTEGER[],
+  valid_from TIMESTAMP NOT NULL,
+  valid_until TIMESTAMP NOT NULL,
+  PRIMARY KEY (id_spot, valid_until),
+  CONSTRAINT fk_spot
+    FOREIGN KEY (id_spot)
+      REFERENCES spots (id)
+);
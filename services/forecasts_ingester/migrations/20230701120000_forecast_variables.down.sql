@@ -0,0 +1,17 @@
+ALTER TABLE forecasts DROP COLUMN IF EXISTS temperature_model;
+ALTER TABLE forecasts DROP COLUMN IF EXISTS sea_level_pressure;
+ALTER TABLE forecasts DROP COLUMN IF EXISTS freezing_level;
+ALTER TABLE forecasts DROP COLUMN IF EXISTS snow_line;
+ALTER TABLE forecasts DROP COLUMN IF EXISTS cloud_cover_total;
+ALTER TABLE forecasts DROP COLUMN IF EXISTS precipitation_amount;
+ALTER TABLE forecasts DROP COLUMN IF EXISTS precipitation_amount_1h;
+
+COMMENT ON COLUMN forecasts.gust IS NULL;
+COMMENT ON COLUMN forecasts.wind_speed IS NULL;
+COMMENT ON COLUMN forecasts.wind_direction IS NULL;
+COMMENT ON COLUMN forecasts.temperature IS NULL;
+COMMENT ON COLUMN forecasts.relative_humidity IS NULL;
+COMMENT ON COLUMN forecasts.cloud_cover_high IS NULL;
+COMMENT ON COLUMN forecasts.cloud_cover_mid IS NULL;
+COMMENT ON COLUMN forecasts.cloud_cover_low IS NULL;
+COMMENT ON COLUMN forecasts.precipitation IS NULL;
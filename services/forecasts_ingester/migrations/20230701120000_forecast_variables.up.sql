@@ -0,0 +1,25 @@
+-- remaining variables of windguru forecasts, see `Fcst` for the mapping of their names
+ALTER TABLE forecasts ADD COLUMN IF NOT EXISTS temperature_model REAL;
+ALTER TABLE forecasts ADD COLUMN IF NOT EXISTS sea_level_pressure INT;
+ALTER TABLE forecasts ADD COLUMN IF NOT EXISTS freezing_level INT;
+ALTER TABLE forecasts ADD COLUMN IF NOT EXISTS snow_line INT;
+ALTER TABLE forecasts ADD COLUMN IF NOT EXISTS cloud_cover_total INT;
+ALTER TABLE forecasts ADD COLUMN IF NOT EXISTS precipitation_amount REAL;
+ALTER TABLE forecasts ADD COLUMN IF NOT EXISTS precipitation_amount_1h REAL;
+
+COMMENT ON COLUMN forecasts.gust IS 'GUST, knots';
+COMMENT ON COLUMN forecasts.wind_speed IS 'WINDSPD, knots';
+COMMENT ON COLUMN forecasts.wind_direction IS 'WINDDIR, degrees the wind blows from';
+COMMENT ON COLUMN forecasts.temperature IS 'TMPE, °C at the spot altitude';
+COMMENT ON COLUMN forecasts.temperature_model IS 'TMP, °C at the model terrain altitude';
+COMMENT ON COLUMN forecasts.relative_humidity IS 'RH, %';
+COMMENT ON COLUMN forecasts.sea_level_pressure IS 'SLP, hPa';
+COMMENT ON COLUMN forecasts.freezing_level IS 'FLHGT, m';
+COMMENT ON COLUMN forecasts.snow_line IS 'SLHGT, m';
+COMMENT ON COLUMN forecasts.cloud_cover_total IS 'TCDC, %';
+COMMENT ON COLUMN forecasts.cloud_cover_high IS 'HCDC, %';
+COMMENT ON COLUMN forecasts.cloud_cover_mid IS 'MCDC, %';
+COMMENT ON COLUMN forecasts.cloud_cover_low IS 'LCDC, %';
+COMMENT ON COLUMN forecasts.precipitation_amount IS 'APCP, mm over the model step';
+COMMENT ON COLUMN forecasts.precipitation_amount_1h IS 'APCP1, mm over the last hour';
+COMMENT ON COLUMN forecasts.precipitation IS 'PCPT, precipitation type code';
@@ -0,0 +1,10 @@
+-- without the window in the key only readings of the default one can be kept
+ALTER TABLE station_readings DROP CONSTRAINT IF EXISTS station_readings_pkey;
+DELETE FROM station_readings WHERE avg_minutes <> 5;
+ALTER TABLE station_readings ADD PRIMARY KEY (id_station, time);
+
+ALTER TABLE station_readings DROP COLUMN IF EXISTS avg_minutes;
+ALTER TABLE station_readings DROP COLUMN IF EXISTS mean_sea_level_pressure;
+ALTER TABLE station_readings DROP COLUMN IF EXISTS relative_humidity;
+ALTER TABLE station_readings DROP COLUMN IF EXISTS gustiness;
+ALTER TABLE station_readings DROP COLUMN IF EXISTS wind_min;
@@ -0,0 +1,12 @@
+-- every observed field of a reading, keyed also by the window it was averaged over
+ALTER TABLE station_readings ADD COLUMN IF NOT EXISTS wind_min REAL;
+ALTER TABLE station_readings ADD COLUMN IF NOT EXISTS gustiness REAL;
+ALTER TABLE station_readings ADD COLUMN IF NOT EXISTS relative_humidity INT;
+ALTER TABLE station_readings ADD COLUMN IF NOT EXISTS mean_sea_level_pressure REAL;
+
+-- readings stored so far were fetched with the default window
+ALTER TABLE station_readings ADD COLUMN IF NOT EXISTS avg_minutes INT NOT NULL DEFAULT 5;
+ALTER TABLE station_readings ALTER COLUMN avg_minutes DROP DEFAULT;
+
+ALTER TABLE station_readings DROP CONSTRAINT IF EXISTS station_readings_pkey;
+ALTER TABLE station_readings ADD PRIMARY KEY (id_station, avg_minutes, time);
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS wave_forecasts;
@@ -0,0 +1,33 @@
+-- forecasts of wave models, see `WaveFcst` for the mapping of windguru variables
+CREATE TABLE IF NOT EXISTS wave_forecasts (
+  id_spot INT NOT NULL,
+  id_model INT NOT NULL,
+  forecast_from TIMESTAMP NOT NULL,
+  forecast_for TIMESTAMP NOT NULL,
+  wave_height REAL,
+  wave_period REAL,
+  wave_direction INT,
+  wind_wave_height REAL,
+  wind_wave_period REAL,
+  wind_wave_direction INT,
+  swell1_height REAL,
+  swell1_period REAL,
+  swell1_direction INT,
+  swell2_height REAL,
+  swell2_period REAL,
+  swell2_direction INT,
+  PRIMARY KEY (id_spot, id_model, forecast_from, forecast_for),
+  CONSTRAINT fk_model
+    FOREIGN KEY (id_model)
+      REFERENCES models (id),
+  CONSTRAINT fk_spot
+    FOREIGN KEY (id_spot)
+      REFERENCES spots (id)
+);
+
+COMMENT ON COLUMN wave_forecasts.wave_height IS 'HTSGW, significant height of combined waves in m';
+COMMENT ON COLUMN wave_forecasts.wave_period IS 'PERPW, peak period in s';
+COMMENT ON COLUMN wave_forecasts.wave_direction IS 'DIRPW, degrees the waves come from';
+COMMENT ON COLUMN wave_forecasts.wind_wave_height IS 'WVHGT, m';
+COMMENT ON COLUMN wave_forecasts.swell1_height IS 'SWELL1, m';
+COMMENT ON COLUMN wave_forecasts.swell2_height IS 'SWELL2, m';
@@ -0,0 +1,4 @@
+ALTER TABLE forecasts DROP CONSTRAINT IF EXISTS fk_model_run;
+ALTER TABLE wave_forecasts DROP CONSTRAINT IF EXISTS fk_model_run;
+
+DROP TABLE IF EXISTS model_runs;
@@ -0,0 +1,32 @@
+-- runs of each model which were fetched, forecasts are issued at the init time of their run
+CREATE TABLE IF NOT EXISTS model_runs (
+  id_model INT NOT NULL,
+  init_time TIMESTAMP NOT NULL,
+  rundef VARCHAR(255),
+  hr_start INT,
+  hr_end INT,
+  hr_step INT,
+  update_last TIMESTAMP,
+  fetched_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (id_model, init_time),
+  CONSTRAINT fk_model
+    FOREIGN KEY (id_model)
+      REFERENCES models (id)
+);
+
+-- forecasts stored so far were issued at the last update of their run, which is all
+-- that is known about those runs
+INSERT INTO model_runs (id_model, init_time, update_last)
+  SELECT DISTINCT id_model, forecast_from, forecast_from FROM forecasts
+  UNION
+  SELECT DISTINCT id_model, forecast_from, forecast_from FROM wave_forecasts
+ON CONFLICT DO NOTHING;
+
+ALTER TABLE forecasts
+  ADD CONSTRAINT fk_model_run
+    FOREIGN KEY (id_model, forecast_from)
+      REFERENCES model_runs (id_model, init_time);
+ALTER TABLE wave_forecasts
+  ADD CONSTRAINT fk_model_run
+    FOREIGN KEY (id_model, forecast_from)
+      REFERENCES model_runs (id_model, init_time);
@@ -0,0 +1,17 @@
+-- runs of a model whose forecasts were ingested for a spot, written in the transaction
+-- storing the forecasts so a recorded run is complete
+CREATE TABLE IF NOT EXISTS spot_model_runs (
+  id_spot INT NOT NULL,
+  id_model INT NOT NULL,
+  init_time TIMESTAMP NOT NULL,
+  rundef VARCHAR(255) NOT NULL,
+  update_last TIMESTAMP NOT NULL,
+  ingested_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (id_spot, id_model, init_time),
+  CONSTRAINT fk_spot
+    FOREIGN KEY (id_spot)
+      REFERENCES spots (id),
+  CONSTRAINT fk_model_run
+    FOREIGN KEY (id_model, init_time)
+      REFERENCES model_runs (id_model, init_time)
+);
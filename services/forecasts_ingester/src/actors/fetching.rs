@@ -1,10 +1,8 @@
-use crate::data_fetcher::errors::FetchError;
-use crate::data_fetcher::DataFetcher;
+use crate::data_fetcher::{DataFetcher, FetchResults};
 
 use actix::*;
 
 use super::messages::fetching::FetchMsg;
-use super::messages::ingesting::IngestMsg;
 
 pub struct FetchingActor<DF: DataFetcher> {
     fetcher: DF,
@@ -27,16 +25,12 @@ impl<DF> Handler<FetchMsg> for FetchingActor<DF>
 where
     DF: DataFetcher + Clone + 'static,
 {
-    type Result = ResponseFuture<Result<IngestMsg, FetchError>>;
+    type Result = ResponseFuture<FetchResults>;
 
     fn handle(&mut self, msg: FetchMsg, _ctx: &mut Context<Self>) -> Self::Result {
         Box::pin({
             let fetcher = self.fetcher.clone();
-            async move {
-                let ingest_msg = fetcher.fetch(msg).await?;
-
-                Ok(ingest_msg)
-            }
+            async move { fetcher.fetch(msg).await }
         })
     }
 }
@@ -1,4 +1,4 @@
-use crate::data_ingester::{errors::IngestError, DataIngester};
+use crate::data_ingester::{errors::IngestError, DataIngester, IngestCounts};
 
 use actix::{Actor, Context, Handler, ResponseFuture};
 
@@ -25,7 +25,7 @@ impl<D> Handler<IngestMsg> for IngestingActor<D>
 where
     D: DataIngester + Clone + Unpin + 'static,
 {
-    type Result = ResponseFuture<Result<(), IngestError>>;
+    type Result = ResponseFuture<Result<IngestCounts, IngestError>>;
 
     fn handle(&mut self, msg: IngestMsg, _ctx: &mut Context<Self>) -> Self::Result {
         Box::pin({
@@ -1,13 +1,14 @@
+use std::collections::BTreeMap;
 use std::fmt::Display;
 
-use super::ingesting::IngestMsg;
-use crate::data_fetcher::errors::FetchError;
-use crate::types::windguru::forecast::IdSpot;
+use crate::data_fetcher::FetchResults;
+use crate::data_ingester::IngestedRun;
+use crate::types::windguru::forecast::{IdModel, IdSpot};
 use crate::types::windguru::station::WindguruStationFetchParams;
 use actix::Message;
 
 #[derive(Message)]
-#[rtype(result = "Result<IngestMsg, FetchError>")]
+#[rtype(result = "FetchResults")]
 pub enum FetchMsg {
     WindguruForecast(WindguruForecastFetchMsg),
     WindguruStation(WindguruStationFetchParams),
@@ -15,6 +16,11 @@ pub enum FetchMsg {
 
 pub struct WindguruForecastFetchMsg {
     pub spot: IdSpot,
+    /// Models to fetch, every model offered for the spot when empty
+    pub models: Vec<IdModel>,
+    /// Latest completely stored run of each model, such runs are not downloaded again
+    /// unless windguru updated them since
+    pub ingested_runs: BTreeMap<IdModel, IngestedRun>,
 }
 
 impl Display for FetchMsg {
@@ -22,7 +28,6 @@ impl Display for FetchMsg {
         match self {
             FetchMsg::WindguruForecast(_) => write!(f, "WindguruForecastFetchMsg"),
             FetchMsg::WindguruStation(_) => write!(f, "WindguruStationFetchMsg"),
-            _ => unimplemented!(),
         }
     }
 }
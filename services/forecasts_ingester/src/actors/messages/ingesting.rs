@@ -1,29 +1,38 @@
 use std::fmt::Display;
 
-use crate::data_ingester::errors::IngestError;
+use crate::data_ingester::{errors::IngestError, IngestCounts};
 use crate::types::windguru::forecast::{Spot, WindguruForecasts};
-use crate::types::windguru::station::WindguruStationData;
+use crate::types::windguru::station::{SpotStation, WindguruStationData};
+use crate::types::windguru::wave::WindguruWaveForecasts;
 use actix::Message;
 
 #[derive(Message)]
-#[rtype(result = "Result<(), IngestError>")]
+#[rtype(result = "Result<IngestCounts, IngestError>")]
 pub enum IngestMsg {
     WindguruForecast(WindguruForecast),
+    WindguruWaveForecast(WindguruWaveForecast),
     WindguruStationReading(i64, WindguruStationData),
-    WindguruSpot(Spot)
+    WindguruSpotStations(Vec<SpotStation>),
 }
 
+/// Forecasts reference their spot, so it is ingested along with them
 pub struct WindguruForecast {
+    pub spot: Spot,
     pub forecast: WindguruForecasts,
 }
 
+pub struct WindguruWaveForecast {
+    pub spot: Spot,
+    pub forecast: WindguruWaveForecasts,
+}
+
 impl Display for IngestMsg {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             IngestMsg::WindguruForecast(_) => write!(f, "WindguruForecastIngestMsg"),
+            IngestMsg::WindguruWaveForecast(_) => write!(f, "WindguruWaveForecastIngestMsg"),
             IngestMsg::WindguruStationReading(_, _) => write!(f, "WindguruStationIngestMsg"),
-            IngestMsg::WindguruSpot(_) => write!(f, "WindguruSpotMsg"),
-            _ => unimplemented!(),
+            IngestMsg::WindguruSpotStations(_) => write!(f, "WindguruSpotStationsMsg"),
         }
     }
 }
@@ -0,0 +1,26 @@
+pub mod verification;
+pub mod volatility;
+
+/// Smallest angle between two directions in degrees
+pub fn direction_delta(a: f32, b: f32) -> f32 {
+    let delta = (a - b).rem_euclid(360.0);
+    delta.min(360.0 - delta)
+}
+
+/// Running arithmetic mean
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Mean {
+    sum: f64,
+    count: usize,
+}
+
+impl Mean {
+    pub fn push(&mut self, value: f32) {
+        self.sum += value as f64;
+        self.count += 1;
+    }
+
+    pub fn value(&self) -> Option<f32> {
+        (self.count > 0).then(|| (self.sum / self.count as f64) as f32)
+    }
+}
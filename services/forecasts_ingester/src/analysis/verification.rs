@@ -0,0 +1,388 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use clap::Args;
+use sqlx::{PgPool, QueryBuilder};
+
+use super::{direction_delta, Mean};
+use crate::{
+    data_ingester::postgres_repository::rows_per_statement,
+    types::windguru::{
+        forecast::{IdModel, IdSpot},
+        station::IdStation,
+    },
+};
+
+/// Compares forecasts with readings of the stations mapped to their spots
+#[derive(Args, Debug)]
+pub struct VerificationParams {
+    /// Spot to verify, every spot when omitted
+    #[arg(long)]
+    pub spot: Option<IdSpot>,
+    /// Model to verify, every model when omitted
+    #[arg(long)]
+    pub model: Option<IdModel>,
+    /// First day of forecasted hours
+    #[arg(long)]
+    pub from: NaiveDate,
+    /// Last day of forecasted hours, inclusive
+    #[arg(long)]
+    pub to: NaiveDate,
+}
+
+impl VerificationParams {
+    fn period(&self) -> (NaiveDateTime, NaiveDateTime) {
+        (
+            self.from.and_hms_opt(0, 0, 0).unwrap(),
+            self.to.succ_opt().unwrap().and_hms_opt(0, 0, 0).unwrap(),
+        )
+    }
+}
+
+/// Forecast paired with station readings over the forecast step around its hour, averaged
+/// except for the gust which is their peak
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct ForecastObservation {
+    pub id_spot: IdSpot,
+    pub id_model: IdModel,
+    pub id_station: IdStation,
+    pub forecast_from: NaiveDateTime,
+    pub forecast_for: NaiveDateTime,
+    pub wind_speed: Option<f32>,
+    pub gust: Option<f32>,
+    pub wind_direction: Option<i32>,
+    pub observed_wind_speed: Option<f64>,
+    pub observed_gust: Option<f64>,
+    pub observed_wind_direction: Option<f64>,
+}
+
+/// Mean absolute error, root mean square error and bias (forecast - observation)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ErrorStats {
+    pub mae: f32,
+    pub rmse: f32,
+    pub bias: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeadTimeVerification {
+    pub id_spot: IdSpot,
+    pub id_model: IdModel,
+    pub id_station: IdStation,
+    pub lead_hours: i64,
+    pub samples: usize,
+    pub wind_speed: Option<ErrorStats>,
+    pub gust: Option<ErrorStats>,
+    /// Mean absolute angle between forecasted and observed direction
+    pub wind_direction_mae: Option<f32>,
+}
+
+#[derive(Default)]
+struct ErrorAccumulator {
+    abs: Mean,
+    squared: Mean,
+    diff: Mean,
+}
+
+impl ErrorAccumulator {
+    fn push(&mut self, forecast: f32, observed: f32) {
+        let diff = forecast - observed;
+
+        self.abs.push(diff.abs());
+        self.squared.push(diff * diff);
+        self.diff.push(diff);
+    }
+
+    fn stats(&self) -> Option<ErrorStats> {
+        Some(ErrorStats {
+            mae: self.abs.value()?,
+            rmse: self.squared.value()?.sqrt(),
+            bias: self.diff.value()?,
+        })
+    }
+}
+
+#[derive(Default)]
+struct VerificationAccumulator {
+    samples: usize,
+    wind_speed: ErrorAccumulator,
+    gust: ErrorAccumulator,
+    wind_direction: Mean,
+}
+
+/// Station readings are stored in UTC, same as forecasted hours, and averaged over the
+/// model step centered at the forecasted hour. Direction is averaged as a unit vector.
+pub async fn load_forecast_observations(
+    pool: &PgPool,
+    params: &VerificationParams,
+) -> Result<Vec<ForecastObservation>, sqlx::Error> {
+    let (from, to) = params.period();
+
+    sqlx::query_as(
+        r#"SELECT
+            f.id_spot,
+            f.id_model,
+            ss.id_station,
+            f.forecast_from,
+            f.forecast_for,
+            f.wind_speed,
+            f.gust,
+            f.wind_direction,
+            AVG(r.wind_speed_avg)::FLOAT8 AS observed_wind_speed,
+            MAX(r.wind_max)::FLOAT8 AS observed_gust,
+            DEGREES(ATAN2(
+                AVG(SIN(RADIANS(r.wind_direction))),
+                AVG(COS(RADIANS(r.wind_direction)))
+            )) AS observed_wind_direction
+        FROM forecasts f
+        JOIN models m ON m.id = f.id_model
+        JOIN spot_stations ss ON ss.id_spot = f.id_spot
+        JOIN station_readings r ON r.id_station = ss.id_station
+            AND r.avg_minutes = ss.avg_minutes
+            AND r.time >= f.forecast_for - make_interval(mins => COALESCE(m.hr_step, 1) * 30)
+            AND r.time < f.forecast_for + make_interval(mins => COALESCE(m.hr_step, 1) * 30)
+        WHERE f.forecast_for >= $1
+            AND f.forecast_for < $2
+            AND ($3::INT IS NULL OR f.id_spot = $3)
+            AND ($4::INT IS NULL OR f.id_model = $4)
+        GROUP BY f.id_spot, f.id_model, ss.id_station, f.forecast_from, f.forecast_for,
+            f.wind_speed, f.gust, f.wind_direction"#,
+    )
+    .bind(from)
+    .bind(to)
+    .bind(params.spot)
+    .bind(params.model)
+    .fetch_all(pool)
+    .await
+}
+
+/// Aggregates errors per spot, model, station and lead time
+pub fn compute_verification(observations: &[ForecastObservation]) -> Vec<LeadTimeVerification> {
+    let mut accumulators: BTreeMap<(IdSpot, IdModel, IdStation, i64), VerificationAccumulator> =
+        BTreeMap::new();
+
+    for obs in observations {
+        let lead_hours = (obs.forecast_for - obs.forecast_from).num_hours();
+        if lead_hours < 0 {
+            continue;
+        }
+
+        let acc = accumulators
+            .entry((obs.id_spot, obs.id_model, obs.id_station, lead_hours))
+            .or_default();
+
+        acc.samples += 1;
+        if let (Some(forecast), Some(observed)) = (obs.wind_speed, obs.observed_wind_speed) {
+            acc.wind_speed.push(forecast, observed as f32);
+        }
+        if let (Some(forecast), Some(observed)) = (obs.gust, obs.observed_gust) {
+            acc.gust.push(forecast, observed as f32);
+        }
+        if let (Some(forecast), Some(observed)) = (obs.wind_direction, obs.observed_wind_direction)
+        {
+            acc.wind_direction
+                .push(direction_delta(forecast as f32, observed as f32));
+        }
+    }
+
+    accumulators
+        .into_iter()
+        .map(
+            |((id_spot, id_model, id_station, lead_hours), acc)| LeadTimeVerification {
+                id_spot,
+                id_model,
+                id_station,
+                lead_hours,
+                samples: acc.samples,
+                wind_speed: acc.wind_speed.stats(),
+                gust: acc.gust.stats(),
+                wind_direction_mae: acc.wind_direction.value(),
+            },
+        )
+        .collect()
+}
+
+/// Columns of the forecast_verification table written for every lead time
+const VERIFICATION_COLUMNS: &[&str] = &[
+    "id_spot",
+    "id_model",
+    "id_station",
+    "lead_hours",
+    "period_from",
+    "period_to",
+    "samples",
+    "wind_speed_mae",
+    "wind_speed_rmse",
+    "wind_speed_bias",
+    "gust_mae",
+    "gust_rmse",
+    "gust_bias",
+    "wind_direction_mae",
+];
+
+/// Statements storing the verification, each under the bind parameters limit
+fn verification_chunks(
+    verification: &[LeadTimeVerification],
+) -> std::slice::Chunks<'_, LeadTimeVerification> {
+    verification.chunks(rows_per_statement(
+        verification.len(),
+        VERIFICATION_COLUMNS.len(),
+    ))
+}
+
+/// Every statement is applied in a single transaction, so a period is never stored partially
+pub async fn store_verification(
+    pool: &PgPool,
+    params: &VerificationParams,
+    verification: &[LeadTimeVerification],
+) -> Result<u64, sqlx::Error> {
+    if verification.is_empty() {
+        return Ok(0);
+    }
+
+    let (period_from, period_to) = params.period();
+    let mut tx = pool.begin().await?;
+    let mut rows_affected = 0;
+
+    for chunk in verification_chunks(verification) {
+        let mut query_builder = QueryBuilder::new(format!(
+            "INSERT INTO forecast_verification({}) ",
+            VERIFICATION_COLUMNS.join(", ")
+        ));
+
+        query_builder.push_values(chunk, |mut b, v| {
+            b.push_bind(v.id_spot)
+                .push_bind(v.id_model)
+                .push_bind(v.id_station)
+                .push_bind(v.lead_hours as i32)
+                .push_bind(period_from)
+                .push_bind(period_to)
+                .push_bind(v.samples as i32)
+                .push_bind(v.wind_speed.map(|s| s.mae))
+                .push_bind(v.wind_speed.map(|s| s.rmse))
+                .push_bind(v.wind_speed.map(|s| s.bias))
+                .push_bind(v.gust.map(|s| s.mae))
+                .push_bind(v.gust.map(|s| s.rmse))
+                .push_bind(v.gust.map(|s| s.bias))
+                .push_bind(v.wind_direction_mae);
+        });
+
+        query_builder.push(
+            r#" ON CONFLICT (id_spot, id_model, id_station, lead_hours, period_from, period_to)
+            DO UPDATE SET
+                samples = EXCLUDED.samples,
+                wind_speed_mae = EXCLUDED.wind_speed_mae,
+                wind_speed_rmse = EXCLUDED.wind_speed_rmse,
+                wind_speed_bias = EXCLUDED.wind_speed_bias,
+                gust_mae = EXCLUDED.gust_mae,
+                gust_rmse = EXCLUDED.gust_rmse,
+                gust_bias = EXCLUDED.gust_bias,
+                wind_direction_mae = EXCLUDED.wind_direction_mae,
+                computed_at = NOW()"#,
+        );
+
+        rows_affected += query_builder
+            .build()
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+    }
+
+    tx.commit().await?;
+    Ok(rows_affected)
+}
+
+impl Display for LeadTimeVerification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fmt_stats = |stats: Option<ErrorStats>| {
+            stats.map_or("-".into(), |s| {
+                format!("mae={:.2} rmse={:.2} bias={:.2}", s.mae, s.rmse, s.bias)
+            })
+        };
+
+        write!(
+            f,
+            "spot={} model={} station={} lead={}h samples={} wind_speed[{}] gust[{}] direction_mae={}",
+            self.id_spot,
+            self.id_model,
+            self.id_station,
+            self.lead_hours,
+            self.samples,
+            fmt_stats(self.wind_speed),
+            fmt_stats(self.gust),
+            self.wind_direction_mae
+                .map_or("-".into(), |mae| format!("{mae:.1}"))
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn observation(
+        forecast: f32,
+        observed: f64,
+        direction: i32,
+        observed_direction: f64,
+    ) -> ForecastObservation {
+        let day = NaiveDate::from_ymd_opt(2023, 4, 8).unwrap();
+        ForecastObservation {
+            id_spot: 36048,
+            id_model: 3,
+            id_station: 2764,
+            forecast_from: day.and_hms_opt(12, 0, 0).unwrap(),
+            forecast_for: day.and_hms_opt(18, 0, 0).unwrap(),
+            wind_speed: Some(forecast),
+            gust: None,
+            wind_direction: Some(direction),
+            observed_wind_speed: Some(observed),
+            observed_gust: None,
+            observed_wind_direction: Some(observed_direction),
+        }
+    }
+
+    #[test]
+    fn computes_errors_per_lead_time() {
+        let observations = vec![
+            observation(12.0, 10.0, 350, 10.0),
+            observation(8.0, 10.0, 90, 60.0),
+        ];
+
+        let verification = compute_verification(&observations);
+
+        assert_eq!(
+            verification,
+            vec![LeadTimeVerification {
+                id_spot: 36048,
+                id_model: 3,
+                id_station: 2764,
+                lead_hours: 6,
+                samples: 2,
+                wind_speed: Some(ErrorStats {
+                    mae: 2.0,
+                    rmse: 2.0,
+                    bias: 0.0,
+                }),
+                gust: None,
+                wind_direction_mae: Some(25.0),
+            }]
+        );
+    }
+
+    #[test]
+    fn split_verification_under_bind_limit() {
+        let row = compute_verification(&[observation(12.0, 10.0, 350, 10.0)]).remove(0);
+        let verification = vec![row; 10_000];
+
+        let chunks: Vec<usize> = verification_chunks(&verification).map(<[_]>::len).collect();
+
+        assert_eq!(chunks, vec![4681, 4681, 638]);
+        assert!(chunks
+            .iter()
+            .all(|rows| rows * VERIFICATION_COLUMNS.len() <= u16::MAX as usize));
+    }
+}
@@ -0,0 +1,279 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use clap::Args;
+use sqlx::PgPool;
+
+use super::{direction_delta, Mean};
+use crate::types::windguru::forecast::{IdModel, IdSpot};
+
+/// Computes how forecasts for the same target hour change between consecutive model runs
+#[derive(Args, Debug)]
+pub struct VolatilityParams {
+    /// Spot to analyse, every spot when omitted
+    #[arg(long)]
+    pub spot: Option<IdSpot>,
+    /// Model to analyse, every model when omitted
+    #[arg(long)]
+    pub model: Option<IdModel>,
+    /// First day of forecasted hours
+    #[arg(long)]
+    pub from: NaiveDate,
+    /// Last day of forecasted hours, inclusive
+    #[arg(long)]
+    pub to: NaiveDate,
+    #[command(flatten)]
+    pub thresholds: VolatilityThresholds,
+}
+
+/// Mean run to run changes below which a forecast is considered stable
+#[derive(Args, Debug, Clone, Copy)]
+pub struct VolatilityThresholds {
+    /// Wind speed change in knots
+    #[arg(long = "wind-speed-threshold", default_value_t = 1.0)]
+    pub wind_speed: f32,
+    /// Gust change in knots
+    #[arg(long = "gust-threshold", default_value_t = 1.5)]
+    pub gust: f32,
+    /// Wind direction change in degrees
+    #[arg(long = "direction-threshold", default_value_t = 10.0)]
+    pub wind_direction: f32,
+}
+
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct ForecastPoint {
+    pub id_spot: IdSpot,
+    pub id_model: IdModel,
+    pub forecast_from: NaiveDateTime,
+    pub forecast_for: NaiveDateTime,
+    pub wind_speed: Option<f32>,
+    pub gust: Option<f32>,
+    pub wind_direction: Option<i32>,
+}
+
+/// Mean absolute change of forecasted values against the previous run at a given lead time
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeadTimeVolatility {
+    pub lead_hours: i64,
+    pub samples: usize,
+    pub wind_speed: Option<f32>,
+    pub gust: Option<f32>,
+    pub wind_direction: Option<f32>,
+}
+
+impl LeadTimeVolatility {
+    fn is_stable(&self, thresholds: &VolatilityThresholds) -> bool {
+        let below =
+            |value: Option<f32>, threshold: f32| !matches!(value, Some(v) if v >= threshold);
+
+        below(self.wind_speed, thresholds.wind_speed)
+            && below(self.gust, thresholds.gust)
+            && below(self.wind_direction, thresholds.wind_direction)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VolatilityReport {
+    pub id_spot: IdSpot,
+    pub id_model: IdModel,
+    pub lead_times: Vec<LeadTimeVolatility>,
+    /// Longest lead time from which every shorter lead time stays below the thresholds
+    pub stable_from_hours: Option<i64>,
+}
+
+#[derive(Default)]
+struct LeadTimeAccumulator {
+    samples: usize,
+    wind_speed: Mean,
+    gust: Mean,
+    wind_direction: Mean,
+}
+
+pub async fn load_forecast_points(
+    pool: &PgPool,
+    params: &VolatilityParams,
+) -> Result<Vec<ForecastPoint>, sqlx::Error> {
+    let from = params.from.and_hms_opt(0, 0, 0).unwrap();
+    let to = params.to.succ_opt().unwrap().and_hms_opt(0, 0, 0).unwrap();
+
+    sqlx::query_as(
+        r#"SELECT id_spot, id_model, forecast_from, forecast_for, wind_speed, gust, wind_direction
+        FROM forecasts
+        WHERE ($1::INT IS NULL OR id_spot = $1)
+            AND ($2::INT IS NULL OR id_model = $2)
+            AND forecast_for >= $3
+            AND forecast_for < $4
+        ORDER BY id_spot, id_model, forecast_for, forecast_from"#,
+    )
+    .bind(params.spot)
+    .bind(params.model)
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await
+}
+
+/// Groups forecasts by spot, model and target hour, and compares every run with the previous one
+pub fn compute_volatility(
+    mut points: Vec<ForecastPoint>,
+    thresholds: &VolatilityThresholds,
+) -> Vec<VolatilityReport> {
+    points.sort_by_key(|p| (p.id_spot, p.id_model, p.forecast_for, p.forecast_from));
+
+    let mut accumulators: BTreeMap<(IdSpot, IdModel), BTreeMap<i64, LeadTimeAccumulator>> =
+        BTreeMap::new();
+
+    for pair in points.windows(2) {
+        let (previous, current) = (&pair[0], &pair[1]);
+        let same_target = previous.id_spot == current.id_spot
+            && previous.id_model == current.id_model
+            && previous.forecast_for == current.forecast_for;
+        let lead_hours = (current.forecast_for - current.forecast_from).num_hours();
+
+        if !same_target || previous.forecast_from == current.forecast_from || lead_hours < 0 {
+            continue;
+        }
+
+        let acc = accumulators
+            .entry((current.id_spot, current.id_model))
+            .or_default()
+            .entry(lead_hours)
+            .or_default();
+
+        acc.samples += 1;
+        if let (Some(a), Some(b)) = (previous.wind_speed, current.wind_speed) {
+            acc.wind_speed.push((a - b).abs());
+        }
+        if let (Some(a), Some(b)) = (previous.gust, current.gust) {
+            acc.gust.push((a - b).abs());
+        }
+        if let (Some(a), Some(b)) = (previous.wind_direction, current.wind_direction) {
+            acc.wind_direction.push(direction_delta(a as f32, b as f32));
+        }
+    }
+
+    accumulators
+        .into_iter()
+        .map(|((id_spot, id_model), leads)| {
+            let lead_times: Vec<LeadTimeVolatility> = leads
+                .into_iter()
+                .map(|(lead_hours, acc)| LeadTimeVolatility {
+                    lead_hours,
+                    samples: acc.samples,
+                    wind_speed: acc.wind_speed.value(),
+                    gust: acc.gust.value(),
+                    wind_direction: acc.wind_direction.value(),
+                })
+                .collect();
+
+            let stable_from_hours = lead_times
+                .iter()
+                .take_while(|lead| lead.is_stable(thresholds))
+                .last()
+                .map(|lead| lead.lead_hours);
+
+            VolatilityReport {
+                id_spot,
+                id_model,
+                lead_times,
+                stable_from_hours,
+            }
+        })
+        .collect()
+}
+
+impl Display for VolatilityReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fmt_value = |value: Option<f32>| value.map_or("-".into(), |v| format!("{v:.2}"));
+
+        match self.stable_from_hours {
+            Some(hours) => writeln!(
+                f,
+                "spot={} model={} stable from {}h before target",
+                self.id_spot, self.id_model, hours
+            )?,
+            None => writeln!(
+                f,
+                "spot={} model={} never stable",
+                self.id_spot, self.id_model
+            )?,
+        }
+
+        writeln!(
+            f,
+            "{:>8} {:>8} {:>10} {:>10} {:>10}",
+            "lead_h", "samples", "wind_speed", "gust", "direction"
+        )?;
+        for lead in &self.lead_times {
+            writeln!(
+                f,
+                "{:>8} {:>8} {:>10} {:>10} {:>10}",
+                lead.lead_hours,
+                lead.samples,
+                fmt_value(lead.wind_speed),
+                fmt_value(lead.gust),
+                fmt_value(lead.wind_direction)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn point(run_hour: u32, wind_speed: f32, wind_direction: i32) -> ForecastPoint {
+        let day = NaiveDate::from_ymd_opt(2023, 4, 8).unwrap();
+        ForecastPoint {
+            id_spot: 36048,
+            id_model: 3,
+            forecast_from: day.and_hms_opt(run_hour, 0, 0).unwrap(),
+            forecast_for: day.and_hms_opt(18, 0, 0).unwrap(),
+            wind_speed: Some(wind_speed),
+            gust: None,
+            wind_direction: Some(wind_direction),
+        }
+    }
+
+    #[test]
+    fn computes_run_to_run_deltas_by_lead_time() {
+        let thresholds = VolatilityThresholds {
+            wind_speed: 1.0,
+            gust: 1.5,
+            wind_direction: 10.0,
+        };
+        let points = vec![point(0, 10.0, 350), point(6, 14.0, 20), point(12, 14.5, 25)];
+
+        let reports = compute_volatility(points, &thresholds);
+
+        assert_eq!(
+            reports,
+            vec![VolatilityReport {
+                id_spot: 36048,
+                id_model: 3,
+                lead_times: vec![
+                    LeadTimeVolatility {
+                        lead_hours: 6,
+                        samples: 1,
+                        wind_speed: Some(0.5),
+                        gust: None,
+                        wind_direction: Some(5.0),
+                    },
+                    LeadTimeVolatility {
+                        lead_hours: 12,
+                        samples: 1,
+                        wind_speed: Some(4.0),
+                        gust: None,
+                        wind_direction: Some(30.0),
+                    },
+                ],
+                stable_from_hours: Some(6),
+            }]
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use clap::{error::ErrorKind, Args};
+
+use crate::types::windguru::{
+    forecast::IdSpot,
+    station::{IdStation, SpotStation, WindguruStationFetchParams},
+};
+
+/// Ingests historical station readings of a past date range, forecasts can not be
+/// backfilled as Windguru serves only the latest model runs
+#[derive(Args, Debug)]
+pub struct BackfillParams {
+    /// First day of the range
+    #[arg(long)]
+    pub from: NaiveDate,
+    /// Last day of the range, inclusive
+    #[arg(long)]
+    pub to: NaiveDate,
+    /// Stations to backfill, may be repeated
+    #[arg(long = "station")]
+    pub stations: Vec<IdStation>,
+    /// Spots whose watched stations should be backfilled, may be repeated
+    #[arg(long = "spot")]
+    pub spots: Vec<IdSpot>,
+    /// Averaging window of the readings, defaults to the one configured for the station
+    #[arg(long)]
+    pub avg_minutes: Option<u32>,
+    /// Length of a single station data request
+    #[arg(long, default_value_t = 24, value_parser = clap::value_parser!(i64).range(1..))]
+    pub chunk_hours: i64,
+}
+
+impl BackfillParams {
+    /// Clap validates arguments one by one, so the order of the range is checked afterwards
+    pub fn check_range(&self) -> Result<(), clap::Error> {
+        if self.from > self.to {
+            return Err(clap::Error::raw(
+                ErrorKind::ValueValidation,
+                format!(
+                    "--from {} has to be the same day as or before --to {}\n",
+                    self.from, self.to
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn range(&self) -> (DateTime<Utc>, DateTime<Utc>) {
+        let start = self.from.and_hms_opt(0, 0, 0).unwrap();
+        let end = self.to.and_hms_opt(0, 0, 0).unwrap() + Duration::days(1);
+
+        (start.and_utc(), end.and_utc())
+    }
+
+    /// Resolves stations and averaging windows to backfill, every watched
+    /// station when none were selected
+    pub fn select_stations(&self, watched: &[SpotStation]) -> BTreeSet<(IdStation, u32)> {
+        let select_all = self.stations.is_empty() && self.spots.is_empty();
+
+        let mut selected: Vec<(IdStation, u32)> = watched
+            .iter()
+            .filter(|station| {
+                select_all
+                    || self.spots.contains(&station.id_spot)
+                    || self.stations.contains(&station.id_station)
+            })
+            .map(|station| (station.id_station, station.avg_minutes))
+            .collect();
+
+        // stations asked for explicitly do not have to be mapped to any spot
+        let default_avg_minutes = WindguruStationFetchParams::default().avg_minutes;
+        let unmapped: Vec<IdStation> = self
+            .stations
+            .iter()
+            .filter(|id_station| !selected.iter().any(|(id, _)| id == *id_station))
+            .copied()
+            .collect();
+        selected.extend(
+            unmapped
+                .into_iter()
+                .map(|id_station| (id_station, default_avg_minutes)),
+        );
+
+        selected
+            .into_iter()
+            .map(|(id_station, avg_minutes)| (id_station, self.avg_minutes.unwrap_or(avg_minutes)))
+            .collect()
+    }
+}
+
+/// Chunk is considered present when every averaging window within it has a stored reading
+pub fn is_chunk_present(
+    stored_readings: u64,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    avg_minutes: u32,
+) -> bool {
+    let expected = (to - from).num_minutes() / i64::from(avg_minutes.max(1));
+
+    stored_readings >= expected.max(0) as u64
+}
+
+/// Splits the range into consecutive chunks no longer than `chunk`
+pub fn split_into_chunks(
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    chunk: Duration,
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let mut chunks = Vec::new();
+    let mut chunk_start = from;
+
+    while chunk_start < to {
+        let chunk_end = (chunk_start + chunk).min(to);
+        chunks.push((chunk_start, chunk_end));
+        chunk_start = chunk_end;
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use clap::Parser;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        params: BackfillParams,
+    }
+
+    #[test]
+    fn splits_range_into_chunks() {
+        let from = Utc.with_ymd_and_hms(2023, 4, 1, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2023, 4, 2, 12, 0, 0).unwrap();
+
+        let chunks = split_into_chunks(from, to, Duration::hours(24));
+
+        assert_eq!(
+            chunks,
+            vec![
+                (from, Utc.with_ymd_and_hms(2023, 4, 2, 0, 0, 0).unwrap()),
+                (Utc.with_ymd_and_hms(2023, 4, 2, 0, 0, 0).unwrap(), to),
+            ]
+        );
+    }
+
+    #[test]
+    fn chunk_with_gap_is_missing() {
+        let from = Utc.with_ymd_and_hms(2023, 4, 1, 0, 0, 0).unwrap();
+        let to = from + Duration::hours(1);
+
+        assert!(is_chunk_present(12, from, to, 5));
+        assert!(!is_chunk_present(11, from, to, 5));
+        assert!(!is_chunk_present(0, from, to, 5));
+        assert!(is_chunk_present(1, from, to, 60));
+    }
+
+    #[test]
+    fn rejects_reversed_range() {
+        let parse = |from: &str, to: &str| {
+            Cli::try_parse_from(["backfill", "--from", from, "--to", to])
+                .unwrap()
+                .params
+                .check_range()
+        };
+
+        assert!(parse("2023-04-02", "2023-04-01").is_err());
+        assert!(parse("2023-04-01", "2023-04-01").is_ok());
+        assert!(parse("2023-04-01", "2023-04-02").is_ok());
+    }
+
+    #[test]
+    fn rejects_non_positive_chunk_hours() {
+        let parse = |chunk_hours: &str| {
+            Cli::try_parse_from([
+                "backfill",
+                "--from",
+                "2023-04-01",
+                "--to",
+                "2023-04-02",
+                "--chunk-hours",
+                chunk_hours,
+            ])
+        };
+
+        assert!(parse("0").is_err());
+        assert!(parse("-6").is_err());
+        assert_eq!(parse("6").unwrap().params.chunk_hours, 6);
+    }
+}
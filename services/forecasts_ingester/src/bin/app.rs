@@ -5,6 +5,36 @@ use lib::state::State;
 
 #[cfg(feature = "lambda")]
 use aws_lambda_events::event::cloudwatch_events::CloudWatchEvent;
+#[cfg(not(feature = "lambda"))]
+use clap::{Parser, Subcommand};
+#[cfg(not(feature = "lambda"))]
+use lib::{
+    analysis::{verification::VerificationParams, volatility::VolatilityParams},
+    backfill::BackfillParams,
+    export::ExportParams,
+};
+#[cfg(not(feature = "lambda"))]
+use std::process::ExitCode;
+
+#[cfg(not(feature = "lambda"))]
+#[derive(Parser)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[cfg(not(feature = "lambda"))]
+#[derive(Subcommand)]
+enum Command {
+    /// Ingest station readings of a past date range
+    Backfill(BackfillParams),
+    /// Report how forecasts for the same hour change between model runs
+    Volatility(VolatilityParams),
+    /// Compare forecasts with station readings and store error statistics
+    Verify(VerificationParams),
+    /// Write stored forecasts and station readings to Parquet files
+    Export(ExportParams),
+}
 
 async fn start() {
     let settings = init_config();
@@ -29,8 +59,50 @@ async fn main() -> Result<(), Error> {
 
 #[actix::main]
 #[cfg(not(feature = "lambda"))]
-async fn main() {
+async fn main() -> ExitCode {
     init_logger();
 
-    start().await;
+    match Cli::parse().command {
+        Some(Command::Backfill(params)) => {
+            if let Err(err) = params.check_range() {
+                err.exit();
+            }
+            let settings = init_config();
+            tracing::info!("Starting backfill with params: {:?}", params);
+
+            let failed = State::backfill(settings, params).await;
+            if failed > 0 {
+                tracing::error!(failed, "backfill finished with failed chunks");
+                return ExitCode::FAILURE;
+            }
+        }
+        Some(Command::Volatility(params)) => {
+            let settings = init_config();
+
+            if let Err(err) = State::analyse_volatility(settings, params).await {
+                tracing::error!("volatility analysis failed {:#}", err);
+                return ExitCode::FAILURE;
+            }
+        }
+        Some(Command::Verify(params)) => {
+            let settings = init_config();
+
+            if let Err(err) = State::verify_forecasts(settings, params).await {
+                tracing::error!("forecast verification failed {:#}", err);
+                return ExitCode::FAILURE;
+            }
+        }
+        Some(Command::Export(params)) => {
+            let settings = init_config();
+            tracing::info!("Starting export with params: {:?}", params);
+
+            if let Err(err) = State::export(settings, params).await {
+                tracing::error!("export failed {:#}", err);
+                return ExitCode::FAILURE;
+            }
+        }
+        None => start().await,
+    }
+
+    ExitCode::SUCCESS
 }
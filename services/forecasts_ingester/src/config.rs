@@ -1,4 +1,4 @@
-use std::fmt::Display;
+use std::{fmt::Display, path::PathBuf};
 
 use config::Config;
 use serde::Deserialize;
@@ -41,20 +41,229 @@ impl ConfigCache {
 pub enum DataStorage {
     S3(S3Config),
     Postgresql(PostgresqlConfig),
+    Parquet(ParquetConfig),
 }
 
-#[derive(Deserialize, Debug)]
-pub struct S3Config {}
+#[derive(Deserialize)]
+pub struct S3Config {
+    pub bucket: String,
+    /// Key prefix under which all objects are written
+    #[serde(default)]
+    pub prefix: String,
+    pub region: String,
+    /// Endpoint of an S3 compatible storage, AWS is used when not set
+    pub endpoint_url: Option<String>,
+    /// Static credentials, the default AWS credentials chain is used when not set
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    /// Required by most S3 compatible storages, e.g. MinIO
+    #[serde(default)]
+    pub force_path_style: bool,
+}
+
+/// Settings are logged at startup, so the credentials are redacted
+impl std::fmt::Debug for S3Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let redacted = |secret: &Option<String>| secret.as_ref().map(|_| "<redacted>");
+
+        f.debug_struct("S3Config")
+            .field("bucket", &self.bucket)
+            .field("prefix", &self.prefix)
+            .field("region", &self.region)
+            .field("endpoint_url", &self.endpoint_url)
+            .field("access_key_id", &redacted(&self.access_key_id))
+            .field("secret_access_key", &redacted(&self.secret_access_key))
+            .field("force_path_style", &self.force_path_style)
+            .finish()
+    }
+}
 
 #[derive(Deserialize, Debug)]
 pub struct PostgresqlConfig {
     pub connection_url: String,
+    /// Rows written by a single statement, lowered when the statement would exceed
+    /// the bind parameters limit
+    #[serde(default = "default_batch_rows")]
+    pub batch_rows: usize,
+    #[serde(default)]
+    pub ingest_method: IngestMethod,
+}
+
+/// How rows are written to postgresql, both upsert them the same way
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum IngestMethod {
+    /// Multi-row `INSERT` statements with bound parameters
+    #[default]
+    Insert,
+    /// `COPY` into a staging table merged into the target one, faster for messages with
+    /// many rows such as backfills, while creating the staging table slows down small ones
+    Copy,
+}
+
+fn default_batch_rows() -> usize {
+    1000
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", content = "config")]
+#[serde(rename_all = "lowercase")]
+pub enum WatchListConfig {
+    Api(WatchListApiConfig),
+    Postgresql(PostgresqlConfig),
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WatchListApiConfig {
+    /// Base url of the watcher-settings service
+    pub url: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ParquetConfig {
+    /// Directory under which partitioned files are written
+    pub directory: PathBuf,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SchedulerConfig {
+    /// How often station readings are fetched
+    #[serde(default = "default_station_interval_minutes")]
+    pub station_interval_minutes: i64,
+    /// Time between consecutive runs of a forecast model, used until the period of a model
+    /// is learned from its runs
+    #[serde(default = "default_forecast_run_period_hours")]
+    pub forecast_run_period_hours: i64,
+    /// How long after its init time a model run is expected to be published
+    #[serde(default = "default_forecast_publish_delay_minutes")]
+    pub forecast_publish_delay_minutes: i64,
+    /// Delay before asking again when a new run was not available yet
+    #[serde(default = "default_retry_minutes")]
+    pub retry_minutes: i64,
+}
+
+fn default_station_interval_minutes() -> i64 {
+    30
+}
+
+fn default_forecast_run_period_hours() -> i64 {
+    6
+}
+
+fn default_forecast_publish_delay_minutes() -> i64 {
+    4 * 60
+}
+
+fn default_retry_minutes() -> i64 {
+    30
+}
+
+/// Retries of failed Windguru requests, the backoff doubles with every attempt
+#[derive(Deserialize, Debug, Clone)]
+pub struct RetryConfig {
+    /// Total number of attempts including the first one
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    /// Fraction of the backoff which is randomized, between 0 and 1
+    #[serde(default = "default_jitter")]
+    pub jitter: f64,
+    /// Response statuses worth retrying, timeouts and connection errors are always retried
+    #[serde(default = "default_retryable_statuses")]
+    pub retryable_statuses: Vec<u16>,
+    /// Time a single attempt may take, from connecting until its response is read
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    500
+}
+
+fn default_max_backoff_ms() -> u64 {
+    10_000
+}
+
+fn default_jitter() -> f64 {
+    0.5
+}
+
+fn default_retryable_statuses() -> Vec<u16> {
+    vec![429, 500, 502, 503, 504]
+}
+
+fn default_timeout_ms() -> u64 {
+    60_000
+}
+
+/// Limits of requests sent to Windguru, shared by every kind of fetch
+#[derive(Deserialize, Debug, Clone)]
+pub struct RateLimitConfig {
+    /// Rate at which the token bucket is refilled
+    #[serde(default = "default_requests_per_second")]
+    pub requests_per_second: f64,
+    /// Capacity of the token bucket, number of requests which may be sent at once after idling
+    #[serde(default = "default_burst")]
+    pub burst: u32,
+    #[serde(default = "default_max_in_flight")]
+    pub max_in_flight: usize,
+}
+
+fn default_requests_per_second() -> f64 {
+    2.0
+}
+
+fn default_burst() -> u32 {
+    5
+}
+
+fn default_max_in_flight() -> usize {
+    4
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_second: default_requests_per_second(),
+            burst: default_burst(),
+            max_in_flight: default_max_in_flight(),
+        }
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_max_attempts(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+            jitter: default_jitter(),
+            retryable_statuses: default_retryable_statuses(),
+            timeout_ms: default_timeout_ms(),
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
 pub struct Settings {
     pub windguru: WindguruConfig,
+    /// Retries of failed Windguru requests
+    #[serde(default)]
+    pub retry: RetryConfig,
     pub storage: DataStorage,
+    /// When present the ingester keeps running and fetches on its own schedule,
+    /// otherwise it fetches once and exits
+    pub scheduler: Option<SchedulerConfig>,
+    /// Source of watched spots managed by watcher-settings, the windguru section
+    /// is used when not set or when the source is unreachable
+    pub watch_list: Option<WatchListConfig>,
 }
 
 impl Display for Settings {
@@ -62,3 +271,27 @@ impl Display for Settings {
         write!(f, "{self:#?}")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_s3_credentials() {
+        let config = S3Config {
+            bucket: "forecasts".to_string(),
+            prefix: String::new(),
+            region: "eu-central-1".to_string(),
+            endpoint_url: None,
+            access_key_id: Some("AKIAEXAMPLE".to_string()),
+            secret_access_key: Some("wJalrXUtnFEMI".to_string()),
+            force_path_style: false,
+        };
+
+        let logged = format!("{config:#?}");
+
+        assert!(logged.contains("<redacted>"));
+        assert!(!logged.contains("AKIAEXAMPLE"));
+        assert!(!logged.contains("wJalrXUtnFEMI"));
+    }
+}
@@ -1,7 +1,127 @@
+use std::time::Duration;
+
 use crate::data_fetcher::errors::FetchError;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, SET_COOKIE};
+use tokio::time::Instant;
+
+/// Session is refreshed this long before its cookies expire
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
 
 #[async_trait]
 pub trait Authorizer {
-    async fn authorize(&self) -> Result<(), FetchError>;
+    async fn authorize(&self) -> Result<Session, FetchError>;
+
+    /// Authorizes only when there is no valid session yet, returns generation of the session
+    async fn ensure_authorized(&self) -> Result<SessionGeneration, FetchError>;
+
+    /// Forgets the session of the given generation after it was rejected, a newer session
+    /// already obtained by a concurrent request is kept
+    async fn invalidate_session(&self, generation: SessionGeneration);
+}
+
+/// Counts sessions obtained by a client, tells apart the session a request was sent with
+/// from one refreshed meanwhile
+pub type SessionGeneration = u64;
+
+/// Session of a client shared by its concurrent requests
+#[derive(Debug, Default)]
+pub struct SessionState {
+    pub session: Option<Session>,
+    pub generation: SessionGeneration,
+}
+
+/// Authorization cookies obtained from the main page
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Session {
+    /// Earliest expiry of the received cookies, none when they did not announce one
+    expires_at: Option<Instant>,
+}
+
+impl Session {
+    pub fn from_headers(headers: &HeaderMap, now: Instant, now_utc: DateTime<Utc>) -> Self {
+        let lifetime = headers
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(|value| cookie_lifetime(value, now_utc))
+            .min();
+
+        Self {
+            expires_at: lifetime.map(|lifetime| now + lifetime),
+        }
+    }
+
+    pub fn is_valid(&self, now: Instant) -> bool {
+        match self.expires_at {
+            Some(expires_at) => now + EXPIRY_MARGIN < expires_at,
+            None => true,
+        }
+    }
+}
+
+/// Max-Age takes precedence over Expires, same as in browsers
+fn cookie_lifetime(set_cookie: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let attributes: Vec<(String, &str)> = set_cookie
+        .split(';')
+        .skip(1)
+        .filter_map(|attribute| attribute.split_once('='))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim()))
+        .collect();
+
+    let max_age = attributes
+        .iter()
+        .find(|(name, _)| name == "max-age")
+        .and_then(|(_, value)| value.parse::<i64>().ok())
+        .map(|seconds| Duration::from_secs(seconds.max(0) as u64));
+
+    max_age.or_else(|| {
+        let (_, expires) = attributes.iter().find(|(name, _)| name == "expires")?;
+        let expires = DateTime::parse_from_rfc2822(expires).ok()?;
+
+        Some(
+            (expires.with_timezone(&Utc) - now)
+                .to_std()
+                .unwrap_or_default(),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn session_expires_with_earliest_cookie() {
+        let now = Instant::now();
+        let now_utc = Utc.with_ymd_and_hms(2023, 4, 15, 10, 0, 0).unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.append(
+            SET_COOKIE,
+            HeaderValue::from_static(
+                "session=abc; Domain=localhost; expires=Sat, 15 Apr 2023 13:00:00 GMT; Max-Age=1800; Path=/; secure",
+            ),
+        );
+        headers.append(
+            SET_COOKIE,
+            HeaderValue::from_static("langc=en-; expires=Sat, 15 Apr 2023 10:20:00 GMT; Path=/"),
+        );
+
+        let session = Session::from_headers(&headers, now, now_utc);
+
+        assert_eq!(
+            session,
+            Session {
+                expires_at: Some(now + Duration::from_secs(20 * 60))
+            }
+        );
+        assert!(session.is_valid(now + Duration::from_secs(18 * 60)));
+        assert!(!session.is_valid(now + Duration::from_secs(19 * 60 + 30)));
+    }
 }
@@ -4,15 +4,22 @@ use crate::data_fetcher::FetchError;
 
 use crate::data_fetcher::DataFetcher;
 use crate::data_fetcher::FetchMsg;
-use crate::data_fetcher::IngestMsg;
+use crate::data_fetcher::FetchResults;
 use std::{sync::Arc, time::Duration};
 
 use async_trait::async_trait;
 use reqwest::cookie::CookieStore;
 use reqwest::{cookie::Jar, Client, ClientBuilder, Url};
 
-use super::authorization::Authorizer;
+use super::authorization::{Authorizer, Session, SessionGeneration, SessionState};
+use super::rate_limit::RateLimiter;
+use super::response;
+use super::retry::RetryPolicy;
 use super::windguru;
+use crate::config::{RateLimitConfig, RetryConfig};
+use chrono::Utc;
+use std::future::Future;
+use tokio::{sync::Mutex, time::Instant};
 use tracing::instrument;
 
 #[derive(Debug)]
@@ -20,46 +27,97 @@ pub struct FetchingClient {
     pub client: Client,
     pub url: Url,
     pub jar: Arc<Jar>,
+    pub retry: RetryPolicy,
+    pub limiter: RateLimiter,
+    session: Mutex<SessionState>,
 }
 
 impl FetchingClient {
-    pub fn new(url: String) -> Self {
+    pub fn new(url: String, retry: RetryConfig, rate_limit: &RateLimitConfig) -> Self {
         let jar = Arc::new(Jar::default());
         let client = ClientBuilder::new()
             .connect_timeout(Duration::from_secs(30))
+            .timeout(Duration::from_millis(retry.timeout_ms))
             .cookie_store(true)
             .cookie_provider(Arc::clone(&jar))
             .build()
             .unwrap();
         let url = Url::from_str(&url).unwrap();
 
-        Self { client, url, jar }
+        Self {
+            client,
+            url,
+            jar,
+            retry: retry.into(),
+            limiter: rate_limit.into(),
+            session: Mutex::new(SessionState::default()),
+        }
+    }
+
+    /// Sends an authorized request, the session is refreshed and the request repeated once
+    /// when it gets rejected
+    pub async fn request<T, F, Fut>(&self, operation: &str, mut request: F) -> Result<T, FetchError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, FetchError>>,
+    {
+        let generation = self.ensure_authorized().await?;
+
+        match self.send(operation, &mut request).await {
+            Err(err) if err.is_unauthorized() => {
+                tracing::info!(operation, "request forbidden, refreshing session");
+                self.invalidate_session(generation).await;
+                self.ensure_authorized().await?;
+
+                self.send(operation, &mut request).await
+            }
+            result => result,
+        }
+    }
+
+    /// Sends a request within the rate limits, retrying it according to the retry policy
+    async fn send<T, F, Fut>(&self, operation: &str, mut request: F) -> Result<T, FetchError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, FetchError>>,
+    {
+        self.retry
+            .run(operation, || {
+                let attempt = request();
+                async move {
+                    let _permit = self.limiter.acquire().await;
+                    attempt.await
+                }
+            })
+            .await
     }
 }
 
 #[async_trait]
 impl DataFetcher for FetchingClient {
     #[instrument(skip(self, params))]
-    async fn fetch(&self, params: FetchMsg) -> Result<IngestMsg, FetchError> {
-        self.authorize().await?;
-
+    async fn fetch(&self, params: FetchMsg) -> FetchResults {
         match params {
-            FetchMsg::WindguruForecast(params) => {
-                windguru::forecasts::get_forecast(self, params).await
-            }
+            FetchMsg::WindguruForecast(params) => windguru::forecasts::get_forecast(self, params)
+                .await
+                .unwrap_or_else(|err| vec![Err(err)]),
             FetchMsg::WindguruStation(params) => {
-                windguru::stations::get_station_data(self, params).await
+                vec![windguru::stations::get_station_data(self, params).await]
             }
-            _ => unimplemented!(),
         }
     }
 }
 
 #[async_trait]
 impl Authorizer for FetchingClient {
-    async fn authorize(&self) -> Result<(), FetchError> {
+    async fn authorize(&self) -> Result<Session, FetchError> {
         // Get authorization cookies
-        let response = self.client.get(self.url.clone()).send().await?;
+        let response = self
+            .send("authorize", || async {
+                let response = self.client.get(self.url.clone()).send().await?;
+                response::check_status("authorize", response)
+            })
+            .await?;
 
         let cookies = self.jar.cookies(&self.url);
         let cookies = cookies.ok_or(FetchError::MissingCookies)?;
@@ -70,6 +128,240 @@ impl Authorizer for FetchingClient {
             "Fetching auth cookies"
         );
 
-        Ok(())
+        Ok(Session::from_headers(
+            response.headers(),
+            Instant::now(),
+            Utc::now(),
+        ))
+    }
+
+    async fn ensure_authorized(&self) -> Result<SessionGeneration, FetchError> {
+        // lock is held while authorizing so that concurrent requests share the new session
+        let mut state = self.session.lock().await;
+
+        let valid = matches!(state.session, Some(session) if session.is_valid(Instant::now()));
+        if valid && self.jar.cookies(&self.url).is_some() {
+            return Ok(state.generation);
+        }
+
+        state.session = Some(self.authorize().await?);
+        state.generation += 1;
+        Ok(state.generation)
+    }
+
+    async fn invalidate_session(&self, generation: SessionGeneration) {
+        let mut state = self.session.lock().await;
+
+        if state.generation == generation {
+            state.session = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use pretty_assertions::assert_eq;
+    use reqwest::{header::HeaderMap, StatusCode};
+    use tokio::{sync::Barrier, task::JoinSet};
+
+    use super::*;
+
+    /// Client holding a session which never expires, so requests are sent right away
+    async fn authorized_client(
+        url: &str,
+        retry: RetryConfig,
+        rate_limit: &RateLimitConfig,
+    ) -> FetchingClient {
+        let client = FetchingClient::new(url.to_string(), retry, rate_limit);
+        client.jar.add_cookie_str("session=abc", &client.url);
+        client.session.lock().await.session = Some(Session::from_headers(
+            &HeaderMap::new(),
+            Instant::now(),
+            Utc::now(),
+        ));
+
+        client
+    }
+
+    /// Token bucket is disabled, so only requests in flight are limited
+    fn in_flight_limit(max_in_flight: usize) -> RateLimitConfig {
+        RateLimitConfig {
+            requests_per_second: 0.0,
+            burst: 1,
+            max_in_flight,
+        }
+    }
+
+    #[tokio::test]
+    async fn caps_requests_in_flight_across_messages() {
+        let rate_limit = in_flight_limit(2);
+        let client = Arc::new(
+            authorized_client("http://localhost:8001", RetryConfig::default(), &rate_limit).await,
+        );
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+
+        let mut requests = JoinSet::new();
+        for operation in ["get_forecast_data", "get_station_data"].repeat(3) {
+            let (client, in_flight, max_in_flight) =
+                (client.clone(), in_flight.clone(), max_in_flight.clone());
+
+            requests.spawn(async move {
+                client
+                    .request(operation, || async {
+                        let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                        max_in_flight.fetch_max(current, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                        in_flight.fetch_sub(1, Ordering::SeqCst);
+
+                        Ok(())
+                    })
+                    .await
+            });
+        }
+        while let Some(result) = requests.join_next().await {
+            result.unwrap().unwrap();
+        }
+
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+    }
+
+    /// Serves the main page setting a session cookie, returns its url and how many times
+    /// it was asked for
+    fn serve_authorization() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let authorizations = Arc::new(AtomicUsize::new(0));
+
+        let served = authorizations.clone();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request);
+                served.fetch_add(1, Ordering::SeqCst);
+                let _ = stream.write_all(
+                    b"HTTP/1.1 200 OK\r\nSet-Cookie: session=abc; Path=/\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                );
+            }
+        });
+
+        (url, authorizations)
+    }
+
+    #[tokio::test]
+    async fn retries_stalled_responses() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted = connections.clone();
+        std::thread::spawn(move || {
+            // connections are kept open without ever completing the response
+            let mut stalled = Vec::new();
+            for mut stream in listener.incoming().flatten() {
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request);
+                accepted.fetch_add(1, Ordering::SeqCst);
+                let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n");
+                stalled.push(stream);
+            }
+        });
+        let retry = RetryConfig {
+            max_attempts: 2,
+            initial_backoff_ms: 10,
+            timeout_ms: 200,
+            ..Default::default()
+        };
+        let client = authorized_client(&url, retry, &in_flight_limit(4)).await;
+
+        let result: Result<Vec<u8>, _> = client
+            .request("get_forecast_data", || async {
+                let response = client.client.get(client.url.clone()).send().await?;
+                Ok(response.bytes().await?.to_vec())
+            })
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(FetchError::ErrorFetchingRequest(err)) if err.is_timeout()
+        ));
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+
+    fn forbidden() -> FetchError {
+        FetchError::Unauthorized {
+            endpoint: "get_forecast_data".into(),
+            status: StatusCode::FORBIDDEN,
+        }
+    }
+
+    #[tokio::test]
+    async fn reauthorizes_once_when_forbidden() {
+        let (url, authorizations) = serve_authorization();
+        let client = authorized_client(&url, RetryConfig::default(), &in_flight_limit(4)).await;
+        let attempts = AtomicUsize::new(0);
+
+        let result = client
+            .request("get_forecast_data", || async {
+                match attempts.fetch_add(1, Ordering::SeqCst) {
+                    0 => Err(forbidden()),
+                    _ => Ok(()),
+                }
+            })
+            .await;
+
+        assert!(result.is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(authorizations.load(Ordering::SeqCst), 1);
+
+        // a request rejected again after the refresh is not repeated any more
+        let attempts = AtomicUsize::new(0);
+        let result: Result<(), _> = client
+            .request("get_forecast_data", || async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err(forbidden())
+            })
+            .await;
+
+        assert!(result.unwrap_err().is_unauthorized());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(authorizations.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn concurrently_forbidden_requests_share_refreshed_session() {
+        let (url, authorizations) = serve_authorization();
+        let client =
+            Arc::new(authorized_client(&url, RetryConfig::default(), &in_flight_limit(4)).await);
+        let rejected = Arc::new(Barrier::new(4));
+
+        let mut requests = JoinSet::new();
+        for _ in 0..4 {
+            let (client, rejected) = (client.clone(), rejected.clone());
+
+            requests.spawn(async move {
+                let attempts = AtomicUsize::new(0);
+                client
+                    .request("get_forecast_data", || async {
+                        if attempts.fetch_add(1, Ordering::SeqCst) > 0 {
+                            return Ok(());
+                        }
+                        // every request is rejected with the session it was sent with
+                        rejected.wait().await;
+                        Err(forbidden())
+                    })
+                    .await
+            });
+        }
+        while let Some(result) = requests.join_next().await {
+            result.unwrap().unwrap();
+        }
+
+        assert_eq!(authorizations.load(Ordering::SeqCst), 1);
     }
 }
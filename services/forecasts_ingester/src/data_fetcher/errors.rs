@@ -1,13 +1,48 @@
+use std::time::Duration;
+
+use reqwest::StatusCode;
 use thiserror::Error;
 
+use crate::types::windguru::forecast::{IdModel, IdSpot};
+
 #[derive(Error, Debug)]
 pub enum FetchError {
     #[error("missing cookie header")]
     MissingCookies,
     #[error("unable to parse cookies to str")]
     InvalidCookies(#[from] reqwest::header::ToStrError),
+    #[error("authorization rejected by {endpoint} status={status}")]
+    Unauthorized {
+        endpoint: String,
+        status: StatusCode,
+    },
+    #[error("rate limited by {endpoint} retry_after={retry_after:?}")]
+    RateLimited {
+        endpoint: String,
+        retry_after: Option<Duration>,
+    },
+    #[error("{endpoint} responded with status={status}")]
+    HttpStatus {
+        endpoint: String,
+        status: StatusCode,
+    },
+    #[error("unexpected response from {endpoint} at path={path} err={source}")]
+    InvalidResponse {
+        endpoint: String,
+        path: String,
+        source: serde_json::Error,
+    },
     #[error("sending request failed err={0}")]
     ErrorFetchingRequest(#[from] reqwest::Error),
+    #[error("model {id_model} is not offered for spot {id_spot}")]
+    MissingModel { id_spot: IdSpot, id_model: IdModel },
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
+
+impl FetchError {
+    /// Windguru answers with forbidden once the session cookies are no longer accepted
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(self, FetchError::Unauthorized { .. })
+    }
+}
@@ -9,16 +9,22 @@ use self::errors::FetchError;
 mod authorization;
 pub mod client;
 pub mod errors;
+pub mod rate_limit;
+pub mod response;
+pub mod retry;
 pub mod windguru;
 
+/// Outcome of a single fetch message, one entry for each piece of data it asked for
+pub type FetchResults = Vec<Result<IngestMsg, FetchError>>;
+
 #[async_trait]
 pub trait DataFetcher: Send + Sync + Unpin {
-    async fn fetch(&self, params: FetchMsg) -> Result<IngestMsg, FetchError>;
+    async fn fetch(&self, params: FetchMsg) -> FetchResults;
 }
 
 #[async_trait]
 impl<DF: DataFetcher> DataFetcher for Arc<DF> {
-    async fn fetch(&self, params: FetchMsg) -> Result<IngestMsg, FetchError> {
+    async fn fetch(&self, params: FetchMsg) -> FetchResults {
         self.as_ref().fetch(params).await
     }
 }
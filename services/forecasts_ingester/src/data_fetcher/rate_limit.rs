@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+use tokio::{
+    sync::{Mutex, Semaphore, SemaphorePermit},
+    time::Instant,
+};
+
+use crate::config::RateLimitConfig;
+
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    refill_per_second: f64,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(capacity: u32, refill_per_second: f64, now: Instant) -> Self {
+        let capacity = capacity.max(1) as f64;
+
+        Self {
+            capacity,
+            refill_per_second,
+            tokens: capacity,
+            refilled_at: now,
+        }
+    }
+
+    /// Takes a token, or returns how long to wait until one becomes available
+    fn try_take(&mut self, now: Instant) -> Result<(), Duration> {
+        let elapsed = now
+            .saturating_duration_since(self.refilled_at)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_second).min(self.capacity);
+        self.refilled_at = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - self.tokens) / self.refill_per_second,
+            ))
+        }
+    }
+}
+
+/// Token bucket limiting the rate of requests together with a cap of requests in flight
+#[derive(Debug)]
+pub struct RateLimiter {
+    bucket: Option<Mutex<TokenBucket>>,
+    in_flight: Semaphore,
+}
+
+impl From<&RateLimitConfig> for RateLimiter {
+    fn from(config: &RateLimitConfig) -> Self {
+        // non positive rate disables the bucket
+        let bucket = (config.requests_per_second > 0.0).then(|| {
+            Mutex::new(TokenBucket::new(
+                config.burst,
+                config.requests_per_second,
+                Instant::now(),
+            ))
+        });
+
+        Self {
+            bucket,
+            in_flight: Semaphore::new(config.max_in_flight.max(1)),
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Waits for a free slot and a token, the request is in flight until the permit is dropped
+    pub async fn acquire(&self) -> SemaphorePermit<'_> {
+        let permit = self
+            .in_flight
+            .acquire()
+            .await
+            .expect("rate limiter semaphore is never closed");
+
+        if let Some(bucket) = &self.bucket {
+            loop {
+                let wait = match bucket.lock().await.try_take(Instant::now()) {
+                    Ok(()) => break,
+                    Err(wait) => wait,
+                };
+                tracing::trace!(wait_ms = wait.as_millis() as u64, "waiting for rate limit");
+                tokio::time::sleep(wait).await;
+            }
+        }
+
+        permit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn bucket_allows_burst_then_refills() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2, 4.0, start);
+
+        assert_eq!(bucket.try_take(start), Ok(()));
+        assert_eq!(bucket.try_take(start), Ok(()));
+        assert_eq!(bucket.try_take(start), Err(Duration::from_millis(250)));
+        assert_eq!(bucket.try_take(start + Duration::from_millis(250)), Ok(()));
+    }
+}
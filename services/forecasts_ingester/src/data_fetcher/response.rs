@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use reqwest::{
+    header::{HeaderMap, RETRY_AFTER},
+    Response, StatusCode,
+};
+use serde::de::DeserializeOwned;
+
+use super::errors::FetchError;
+
+/// Turns an unsuccessful response into the matching error
+pub fn check_status(endpoint: &str, response: Response) -> Result<Response, FetchError> {
+    match status_error(endpoint, response.status(), response.headers()) {
+        Some(err) => Err(err),
+        None => Ok(response),
+    }
+}
+
+/// Reads the response body as json, reporting the path at which it did not match `T`
+pub async fn json<T: DeserializeOwned>(
+    endpoint: &str,
+    response: Response,
+) -> Result<T, FetchError> {
+    let body = response.bytes().await?;
+    parse_json(endpoint, &body)
+}
+
+fn status_error(endpoint: &str, status: StatusCode, headers: &HeaderMap) -> Option<FetchError> {
+    let endpoint = endpoint.to_owned();
+
+    match status {
+        status if status.is_success() => None,
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            Some(FetchError::Unauthorized { endpoint, status })
+        }
+        StatusCode::TOO_MANY_REQUESTS => Some(FetchError::RateLimited {
+            endpoint,
+            // only the delay in seconds form is used by windguru
+            retry_after: headers
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse().ok())
+                .map(Duration::from_secs),
+        }),
+        status => Some(FetchError::HttpStatus { endpoint, status }),
+    }
+}
+
+fn parse_json<T: DeserializeOwned>(endpoint: &str, body: &[u8]) -> Result<T, FetchError> {
+    let deserializer = &mut serde_json::Deserializer::from_slice(body);
+
+    serde_path_to_error::deserialize(deserializer).map_err(|err| FetchError::InvalidResponse {
+        endpoint: endpoint.to_owned(),
+        path: err.path().to_string(),
+        source: err.into_inner(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use reqwest::header::HeaderValue;
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[test]
+    fn maps_statuses_and_shape_mismatches_to_errors() {
+        let mut headers = HeaderMap::new();
+        assert!(status_error("get_forecast_data", StatusCode::OK, &headers).is_none());
+        assert!(matches!(
+            status_error("authorize", StatusCode::FORBIDDEN, &headers),
+            Some(FetchError::Unauthorized {
+                status: StatusCode::FORBIDDEN,
+                ..
+            })
+        ));
+        assert!(matches!(
+            status_error("get_forecast_data", StatusCode::BAD_GATEWAY, &headers),
+            Some(FetchError::HttpStatus { endpoint, status: StatusCode::BAD_GATEWAY })
+                if endpoint == "get_forecast_data"
+        ));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("30"));
+        assert!(matches!(
+            status_error("get_station_data", StatusCode::TOO_MANY_REQUESTS, &headers),
+            Some(FetchError::RateLimited { retry_after: Some(retry_after), .. })
+                if retry_after == Duration::from_secs(30)
+        ));
+
+        #[derive(Deserialize, Debug)]
+        struct Station {
+            #[allow(dead_code)]
+            wind_avg: Vec<Option<f32>>,
+        }
+
+        let err =
+            parse_json::<Station>("get_station_data", br#"{"wind_avg": [1.5, "x"]}"#).unwrap_err();
+        match err {
+            FetchError::InvalidResponse { path, .. } => assert_eq!(path, "wind_avg[1]"),
+            err => panic!("unexpected error {err}"),
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::{future::Future, time::Duration};
+
+use rand::Rng;
+use reqwest::StatusCode;
+use tracing::{field, Instrument};
+
+use super::errors::FetchError;
+use crate::config::RetryConfig;
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    config: RetryConfig,
+}
+
+impl From<RetryConfig> for RetryPolicy {
+    fn from(config: RetryConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff before the given retry, `jitter_sample` in [0, 1) shortens it
+    /// by up to the configured jitter fraction
+    fn backoff(&self, retry: u32, jitter_sample: f64) -> Duration {
+        let exponential = self
+            .config
+            .initial_backoff_ms
+            .saturating_mul(2u64.saturating_pow(retry.saturating_sub(1)));
+        let capped = exponential.min(self.config.max_backoff_ms) as f64;
+        let jitter = self.config.jitter.clamp(0.0, 1.0) * jitter_sample;
+
+        Duration::from_millis((capped * (1.0 - jitter)) as u64)
+    }
+
+    fn is_retryable(&self, err: &FetchError) -> bool {
+        let retryable_status =
+            |status: StatusCode| self.config.retryable_statuses.contains(&status.as_u16());
+
+        match err {
+            FetchError::ErrorFetchingRequest(err) => err.is_timeout() || err.is_connect(),
+            FetchError::HttpStatus { status, .. } => retryable_status(*status),
+            FetchError::RateLimited { .. } => retryable_status(StatusCode::TOO_MANY_REQUESTS),
+            _ => false,
+        }
+    }
+
+    /// Runs the request until it succeeds, fails with a non retryable error or runs out of attempts
+    pub async fn run<T, F, Fut>(&self, operation: &str, mut request: F) -> Result<T, FetchError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, FetchError>>,
+    {
+        let max_attempts = self.config.max_attempts.max(1);
+        let mut attempt = 1;
+
+        loop {
+            let span = tracing::debug_span!(
+                "request",
+                operation,
+                attempt,
+                max_attempts,
+                outcome = field::Empty
+            );
+            let result = request().instrument(span.clone()).await;
+
+            match result {
+                Ok(value) => {
+                    span.record("outcome", "success");
+                    return Ok(value);
+                }
+                Err(err) if attempt < max_attempts && self.is_retryable(&err) => {
+                    span.record("outcome", "retry");
+                    let mut backoff = self.backoff(attempt, rand::thread_rng().gen());
+                    if let FetchError::RateLimited {
+                        retry_after: Some(retry_after),
+                        ..
+                    } = &err
+                    {
+                        backoff = backoff.max(*retry_after);
+                    }
+                    tracing::warn!(
+                        operation,
+                        attempt,
+                        backoff_ms = backoff.as_millis() as u64,
+                        "request failed, retrying err={}",
+                        err
+                    );
+
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                Err(err) => {
+                    span.record("outcome", "failure");
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn backoff_grows_exponentially_up_to_the_cap() {
+        let policy = RetryPolicy::from(RetryConfig {
+            max_attempts: 5,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 1500,
+            jitter: 0.5,
+            retryable_statuses: vec![503],
+            timeout_ms: 1000,
+        });
+
+        assert_eq!(policy.backoff(1, 0.0), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2, 0.0), Duration::from_millis(1000));
+        assert_eq!(policy.backoff(3, 0.0), Duration::from_millis(1500));
+        assert_eq!(policy.backoff(2, 1.0), Duration::from_millis(500));
+    }
+}
@@ -1,20 +1,28 @@
 use super::super::errors::FetchError;
+use super::super::FetchResults;
 use crate::{
     actors::messages::{
         fetching::WindguruForecastFetchMsg,
-        ingesting::{IngestMsg, WindguruForecast},
+        ingesting::{IngestMsg, WindguruForecast, WindguruWaveForecast},
     },
     config::Settings,
-    types::windguru::forecast::{ForecastParamsMetadata, IdModel, IdSpot, Spot, WindguruForecasts},
+    data_ingester::IngestedRun,
+    types::windguru::{
+        forecast::{ForecastParamsMetadata, IdModel, IdSpot, Spot, WgModel, WindguruForecasts},
+        wave::WindguruWaveForecasts,
+    },
 };
 
 use super::super::client::FetchingClient;
+use super::super::response;
 
 use super::WINDGURU_REFERER;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 async fn get_spot_metadata(
     fetcher: &FetchingClient,
@@ -27,63 +35,178 @@ async fn get_spot_metadata(
         method: WindguruMethod::ForecastSpot,
     };
 
-    let forecast_spot_response = fetcher
-        .client
-        .get(url)
-        .header("Referer", WINDGURU_REFERER)
-        .query(&query_params)
-        .send()
-        .await?;
+    fetcher
+        .request("get_spot_metadata", || async {
+            let forecast_spot_response = fetcher
+                .client
+                .get(&url)
+                .header("Referer", WINDGURU_REFERER)
+                .query(&query_params)
+                .send()
+                .await?;
 
-    let _response_status = forecast_spot_response.status().as_u16();
-    let forecast_metadata = forecast_spot_response.json().await?;
+            let forecast_spot_response =
+                response::check_status("get_spot_metadata", forecast_spot_response)?;
 
-    Ok(forecast_metadata)
+            response::json("get_spot_metadata", forecast_spot_response).await
+        })
+        .await
 }
 
-async fn get_forecast_data(
+/// Forecasts of wind and wave models come in the same shape, only their variables differ
+async fn get_forecast_data<T: DeserializeOwned>(
     fetcher: &FetchingClient,
     forecast_query_params: &ForecastQueryParams,
-) -> Result<WindguruForecasts, FetchError> {
+) -> Result<T, FetchError> {
     let url = format!("{}/int/iapi.php", fetcher.url);
 
-    let forecast_response = fetcher
-        .client
-        .get(url)
-        .header("Referer", WINDGURU_REFERER)
-        .query(&forecast_query_params)
-        .send()
-        .await?;
-
-    let response_status = forecast_response.status().as_u16();
-    tracing::debug!(response_status = response_status, "Fetching forecast");
+    fetcher
+        .request("get_forecast_data", || async {
+            let forecast_response = fetcher
+                .client
+                .get(&url)
+                .header("Referer", WINDGURU_REFERER)
+                .query(&forecast_query_params)
+                .send()
+                .await?;
 
-    let forecasts = forecast_response.json().await?;
+            let response_status = forecast_response.status().as_u16();
+            tracing::debug!(response_status = response_status, "Fetching forecast");
+            let forecast_response = response::check_status("get_forecast_data", forecast_response)?;
 
-    Ok(forecasts)
+            response::json("get_forecast_data", forecast_response).await
+        })
+        .await
 }
 
+/// Fetches forecasts of every requested model, or of all models offered for the spot
+/// when none were requested, together with wave models driven by them. Each model
+/// yields its own result so a single failing model does not discard the others, every
+/// forecast carries the spot metadata.
 pub async fn get_forecast(
     fetcher: &FetchingClient,
     params: WindguruForecastFetchMsg,
-) -> Result<IngestMsg, FetchError> {
-    let ForecastSpotResponse { models, spots } =
-        get_spot_metadata(fetcher, params.spot).await?;
+) -> Result<FetchResults, FetchError> {
+    let ForecastSpotResponse { models, spots } = get_spot_metadata(fetcher, params.spot).await?;
 
     let spot = Spot::try_from(spots)?;
+    let wave_models = models.wave_models();
+    let requested_wave_models = if params.models.is_empty() {
+        wave_models.clone()
+    } else {
+        models.wave_models_of(&params.models)
+    };
     let forecast_query_params = BTreeMap::<IdModel, ForecastQueryParams>::from(models);
-    let forecast = get_forecast_data(fetcher, forecast_query_params.get(&3).unwrap()).await?;
 
-    Ok(IngestMsg::WindguruForecast(WindguruForecast {
-        forecast,
-    }))
+    let mut requested_models = if params.models.is_empty() {
+        forecast_query_params.keys().copied().collect()
+    } else {
+        params.models
+    };
+    for id_model in &requested_wave_models {
+        if !requested_models.contains(id_model) {
+            requested_models.push(*id_model);
+        }
+    }
+
+    let mut results = Vec::with_capacity(requested_models.len());
+    for id_model in requested_models {
+        let query_params = forecast_query_params.get(&id_model);
+        let ingested_run = params.ingested_runs.get(&id_model);
+        if let Some(query_params) = query_params {
+            if is_offered_run_ingested(ingested_run, query_params) {
+                tracing::debug!(
+                    id_spot = params.spot,
+                    id_model = id_model,
+                    rundef = query_params.rundef,
+                    "run already ingested, skipping download"
+                );
+                continue;
+            }
+        }
+
+        let result = match query_params {
+            Some(query_params) if wave_models.contains(&id_model) => {
+                match get_forecast_data(fetcher, query_params).await {
+                    Ok(WindguruWaveForecasts {
+                        ref wgmodel,
+                        update_last,
+                        ..
+                    }) if is_run_ingested(ingested_run, wgmodel, update_last) => {
+                        tracing::debug!(
+                            id_spot = params.spot,
+                            id_model = id_model,
+                            "run was not updated since ingested, skipping"
+                        );
+                        continue;
+                    }
+                    result => result.map(|forecast| {
+                        IngestMsg::WindguruWaveForecast(WindguruWaveForecast {
+                            spot: spot.clone(),
+                            forecast,
+                        })
+                    }),
+                }
+            }
+            Some(query_params) => match get_forecast_data(fetcher, query_params).await {
+                Ok(WindguruForecasts {
+                    ref wgmodel,
+                    update_last,
+                    ..
+                }) if is_run_ingested(ingested_run, wgmodel, update_last) => {
+                    tracing::debug!(
+                        id_spot = params.spot,
+                        id_model = id_model,
+                        "run was not updated since ingested, skipping"
+                    );
+                    continue;
+                }
+                result => result.map(|forecast| {
+                    IngestMsg::WindguruForecast(WindguruForecast {
+                        spot: spot.clone(),
+                        forecast,
+                    })
+                }),
+            },
+            None => Err(FetchError::MissingModel {
+                id_spot: params.spot,
+                id_model,
+            }),
+        };
+        results.push(result);
+    }
+
+    Ok(results)
+}
+
+/// Offered run needs no download when its hours, given by `rundef`, are those of a
+/// completely stored run. Spot metadata does not tell when the run was last updated.
+fn is_offered_run_ingested(
+    ingested_run: Option<&IngestedRun>,
+    query_params: &ForecastQueryParams,
+) -> bool {
+    ingested_run.is_some_and(|run| {
+        query_params.init_time() == Some(run.init_time) && query_params.rundef == run.rundef
+    })
 }
 
-impl FetchingClient {}
+/// Downloaded run is stored again only when windguru updated it since it was ingested
+fn is_run_ingested(
+    ingested_run: Option<&IngestedRun>,
+    wgmodel: &WgModel,
+    update_last: NaiveDateTime,
+) -> bool {
+    ingested_run
+        .is_some_and(|run| run.init_time == wgmodel.initdate && run.update_last == update_last)
+}
 
 impl From<&Settings> for FetchingClient {
     fn from(settings: &Settings) -> Self {
-        FetchingClient::new(settings.windguru.url.clone())
+        FetchingClient::new(
+            settings.windguru.url.clone(),
+            settings.retry.clone(),
+            &settings.windguru.rate_limit,
+        )
     }
 }
 
@@ -113,6 +236,15 @@ struct ForecastQueryParams {
     pub cachefix: String,
 }
 
+impl ForecastQueryParams {
+    /// Init time of the offered run, `initstr` is given as `%Y%m%d%H`
+    fn init_time(&self) -> Option<DateTime<Utc>> {
+        NaiveDateTime::parse_from_str(&format!("{}00", self.initstr), "%Y%m%d%H%M")
+            .ok()
+            .map(|init_time| init_time.and_utc())
+    }
+}
+
 #[derive(Deserialize, Debug)]
 struct ForecastSpotResponse {
     #[serde(flatten)]
@@ -127,6 +259,25 @@ struct ForecastMetadataWrapper {
     pub models: Vec<ForecastMetadata>,
 }
 
+impl ForecastMetadataWrapper {
+    /// Wave models are offered alongside the wind models they are driven by
+    fn wave_models(&self) -> BTreeSet<IdModel> {
+        self.models
+            .iter()
+            .filter_map(|model| model.id_model_wave)
+            .collect()
+    }
+
+    /// Wave models driven by the given wind models
+    fn wave_models_of(&self, wind_models: &[IdModel]) -> BTreeSet<IdModel> {
+        self.models
+            .iter()
+            .filter(|model| wind_models.contains(&model.id_model))
+            .filter_map(|model| model.id_model_wave)
+            .collect()
+    }
+}
+
 #[derive(Deserialize, Debug)]
 struct ForecastMetadata {
     id_spot: IdSpot,
@@ -134,6 +285,9 @@ struct ForecastMetadata {
     #[serde(rename = "id_model_arr")]
     params: Vec<ForecastParamsMetadata>,
 
+    #[serde(default)]
+    id_model_wave: Option<IdModel>,
+
     #[serde(rename = "options")]
     _options: HashMap<String, Value>,
 }
@@ -144,12 +298,10 @@ impl From<ForecastMetadataWrapper> for BTreeMap<IdModel, ForecastQueryParams> {
             .models
             .into_iter()
             .filter_map(|model| {
-                let Some(model_metadata) = model
+                let model_metadata = model
                     .params
                     .into_iter()
-                    .find(|model_metadata| {
-                        model_metadata.id_model == model.id_model
-                    }) else { return None };
+                    .find(|model_metadata| model_metadata.id_model == model.id_model)?;
 
                 Some((
                     model.id_model,
@@ -169,3 +321,120 @@ impl From<ForecastMetadataWrapper> for BTreeMap<IdModel, ForecastQueryParams> {
             .collect::<BTreeMap<IdModel, ForecastQueryParams>>()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn offered_run(initstr: &str, rundef: &str) -> ForecastQueryParams {
+        ForecastQueryParams {
+            rundef: rundef.to_string(),
+            initstr: initstr.to_string(),
+            forecast_spot: ForecastSpotQueryParams {
+                id_spot: 36048,
+                method: WindguruMethod::Forecast,
+            },
+            id_model: 3,
+            wgcacheable: 21600,
+            cachefix: "".to_string(),
+        }
+    }
+
+    fn ingested_run(update_last: NaiveDateTime) -> IngestedRun {
+        IngestedRun {
+            init_time: Utc.with_ymd_and_hms(2023, 4, 8, 12, 0, 0).unwrap(),
+            rundef: "2023040812x0x240x0x240".to_string(),
+            update_last,
+        }
+    }
+
+    #[test]
+    fn parse_init_time_of_offered_run() {
+        assert_eq!(
+            offered_run(
+                "2023040812",
+                "2023040812x0x240x0x240-2023040800x243x384x255x384"
+            )
+            .init_time(),
+            Some(Utc.with_ymd_and_hms(2023, 4, 8, 12, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn selects_wave_models_of_requested_wind_models() {
+        let tab = |id_model, id_model_wave| ForecastMetadata {
+            id_spot: 36048,
+            id_model,
+            params: vec![],
+            id_model_wave,
+            _options: HashMap::new(),
+        };
+        let models = ForecastMetadataWrapper {
+            models: vec![tab(3, Some(84)), tab(45, Some(85)), tab(59, None)],
+        };
+
+        assert_eq!(models.wave_models(), BTreeSet::from([84, 85]));
+        assert_eq!(models.wave_models_of(&[3, 59]), BTreeSet::from([84]));
+        assert_eq!(models.wave_models_of(&[59]), BTreeSet::new());
+    }
+
+    #[test]
+    fn skips_download_of_ingested_run_with_same_hours() {
+        let update_last = Utc
+            .with_ymd_and_hms(2023, 4, 8, 17, 31, 0)
+            .unwrap()
+            .naive_utc();
+        let run = ingested_run(update_last);
+
+        assert!(is_offered_run_ingested(
+            Some(&run),
+            &offered_run("2023040812", "2023040812x0x240x0x240")
+        ));
+        assert!(!is_offered_run_ingested(
+            Some(&run),
+            &offered_run(
+                "2023040812",
+                "2023040812x0x240x0x240-2023040812x243x384x243x384"
+            )
+        ));
+        assert!(!is_offered_run_ingested(
+            Some(&run),
+            &offered_run("2023040818", "2023040818x0x240x0x240")
+        ));
+        assert!(!is_offered_run_ingested(
+            None,
+            &offered_run("2023040812", "2023040812x0x240x0x240")
+        ));
+    }
+
+    #[test]
+    fn stores_run_updated_since_ingested() {
+        let update_last = Utc
+            .with_ymd_and_hms(2023, 4, 8, 17, 31, 0)
+            .unwrap()
+            .naive_utc();
+        let run = ingested_run(update_last);
+        let wgmodel = WgModel {
+            id_model: 3,
+            model: "gfs".to_string(),
+            model_name: "GFS 13 km".to_string(),
+            initdate: run.init_time,
+            hr_start: 0,
+            hr_end: 240,
+            hr_step: 1,
+            wave: false,
+            rundef: run.rundef.clone(),
+        };
+
+        assert!(is_run_ingested(Some(&run), &wgmodel, update_last));
+        assert!(!is_run_ingested(
+            Some(&run),
+            &wgmodel,
+            update_last + Duration::minutes(40)
+        ));
+        assert!(!is_run_ingested(None, &wgmodel, update_last));
+    }
+}
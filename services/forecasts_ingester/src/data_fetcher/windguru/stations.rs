@@ -1,8 +1,8 @@
 use super::WINDGURU_REFERER;
 use crate::{
     actors::messages::ingesting::IngestMsg,
-    data_fetcher::{client::FetchingClient, errors::FetchError},
-    types::windguru::station::WindguruStationFetchParams,
+    data_fetcher::{client::FetchingClient, errors::FetchError, response},
+    types::windguru::station::{WindguruStationData, WindguruStationFetchParams},
 };
 
 pub async fn get_station_data(
@@ -12,20 +12,28 @@ pub async fn get_station_data(
     let url = format!("{}/int/iapi.php", fetcher.url);
 
     let id_station = params.id_station as i64;
-    let request = fetcher
-        .client
-        .get(url)
-        .header("Referer", WINDGURU_REFERER)
-        .query(&params);
 
-    tracing::debug!("station data request={:?}", request);
+    fetcher
+        .request("get_station_data", || async {
+            let request = fetcher
+                .client
+                .get(&url)
+                .header("Referer", WINDGURU_REFERER)
+                .query(&params);
 
-    let response = request.send().await?;
+            tracing::debug!("station data request={:?}", request);
 
-    let response_status = response.status().as_u16();
-    tracing::debug!(response_status = response_status, "fetching station data");
-    response.error_for_status_ref()?;
+            let response = request.send().await?;
 
-    let station_data = IngestMsg::WindguruStationReading(id_station, response.json().await?);
-    Ok(station_data)
+            let response_status = response.status().as_u16();
+            tracing::debug!(response_status = response_status, "fetching station data");
+            let response = response::check_status("get_station_data", response)?;
+
+            let mut station_data: WindguruStationData =
+                response::json("get_station_data", response).await?;
+            station_data.avg_minutes = params.avg_minutes;
+
+            Ok(IngestMsg::WindguruStationReading(id_station, station_data))
+        })
+        .await
 }
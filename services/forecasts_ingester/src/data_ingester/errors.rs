@@ -8,6 +8,12 @@ pub enum IngestError {
     #[error("persisting data failed err={0}")]
     OtherSqlxError(sqlx::error::Error),
 
+    #[error("writing object failed err={0}")]
+    ObjectStorageError(Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("writing file failed err={0}")]
+    FileStorageError(Box<dyn std::error::Error + Send + Sync>),
+
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -1,15 +1,80 @@
 use async_trait::async_trait;
+use chrono::{DateTime, NaiveDateTime, Utc};
 use errors::IngestError;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 use crate::actors::messages::ingesting::IngestMsg;
+use crate::types::windguru::forecast::{IdModel, IdSpot};
+use crate::types::windguru::station::IdStation;
 
 pub mod errors;
+pub mod parquet_repository;
+mod postgres_copy;
 pub mod postgres_repository;
+pub mod s3_repository;
+
+/// Rows of an ingested message, rows already stored with the same values are skipped
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IngestCounts {
+    pub inserted: u64,
+    pub updated: u64,
+    pub skipped: u64,
+}
+
+impl IngestCounts {
+    /// Storages which only append or overwrite whole objects write every row as new
+    pub fn written(rows: usize) -> Self {
+        Self {
+            inserted: rows as u64,
+            ..Default::default()
+        }
+    }
+
+    /// Rows of a message repeating the key of an earlier row are skipped
+    pub fn duplicates(rows: usize, unique: usize) -> Self {
+        Self {
+            skipped: (rows - unique) as u64,
+            ..Default::default()
+        }
+    }
+}
+
+/// Latest run of a model whose forecasts were completely stored for a spot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IngestedRun {
+    pub init_time: DateTime<Utc>,
+    pub rundef: String,
+    /// Time windguru last updated the run when it was fetched
+    pub update_last: NaiveDateTime,
+}
+
+impl std::ops::AddAssign for IngestCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.inserted += other.inserted;
+        self.updated += other.updated;
+        self.skipped += other.skipped;
+    }
+}
 
 #[async_trait]
 pub trait DataIngester: Send + Sync + Unpin {
-    async fn ingest_forecast(&self, data: IngestMsg) -> Result<(), IngestError>;
+    async fn ingest_forecast(&self, data: IngestMsg) -> Result<IngestCounts, IngestError>;
+
+    /// Number of stored readings of a station averaged over `avg_minutes` within the range
+    async fn station_readings_count(
+        &self,
+        id_station: IdStation,
+        avg_minutes: u32,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<u64, IngestError>;
+
+    /// Latest recorded run of each model whose forecasts were stored for the spot
+    async fn ingested_runs(
+        &self,
+        id_spot: IdSpot,
+    ) -> Result<BTreeMap<IdModel, IngestedRun>, IngestError>;
 }
 
 #[async_trait]
@@ -17,7 +82,26 @@ impl<DI: DataIngester> DataIngester for Arc<DI>
 where
     DI: DataIngester + Send + Sync,
 {
-    async fn ingest_forecast(&self, data: IngestMsg) -> Result<(), IngestError> {
+    async fn ingest_forecast(&self, data: IngestMsg) -> Result<IngestCounts, IngestError> {
         self.as_ref().ingest_forecast(data).await
     }
+
+    async fn station_readings_count(
+        &self,
+        id_station: IdStation,
+        avg_minutes: u32,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<u64, IngestError> {
+        self.as_ref()
+            .station_readings_count(id_station, avg_minutes, from, to)
+            .await
+    }
+
+    async fn ingested_runs(
+        &self,
+        id_spot: IdSpot,
+    ) -> Result<BTreeMap<IdModel, IngestedRun>, IngestError> {
+        self.as_ref().ingested_runs(id_spot).await
+    }
 }
@@ -0,0 +1,957 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    actors::messages::ingesting::{IngestMsg, WindguruForecast, WindguruWaveForecast},
+    config::ParquetConfig,
+    types::windguru::{
+        forecast::{IdModel, IdSpot},
+        station::{IdStation, SpotStation, WindguruStationData},
+    },
+};
+
+use super::errors::IngestError;
+use super::{DataIngester, IngestCounts, IngestedRun};
+
+use arrow_array::{
+    cast::AsArray,
+    types::{Float32Type, Int32Type, TimestampMicrosecondType},
+    ArrayRef, ArrowPrimitiveType, BooleanArray, Float32Array, Int32Array, RecordBatch,
+    TimestampMicrosecondArray,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
+use parquet::{
+    arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ArrowWriter},
+    basic::Compression,
+    errors::ParquetError,
+    file::properties::WriterProperties,
+};
+
+/// Forecast as stored in the `forecasts` table
+#[derive(sqlx::FromRow, Debug, Clone, PartialEq)]
+pub struct ForecastRecord {
+    pub id_spot: IdSpot,
+    pub id_model: IdModel,
+    pub forecast_from: NaiveDateTime,
+    pub forecast_for: NaiveDateTime,
+    pub wave: Option<bool>,
+    pub gust: Option<f32>,
+    pub wind_speed: Option<f32>,
+    pub wind_direction: Option<i32>,
+    pub temperature: Option<f32>,
+    pub relative_humidity: Option<i32>,
+    pub precipitation: Option<i32>,
+    pub cloud_cover_high: Option<i32>,
+    pub cloud_cover_mid: Option<i32>,
+    pub cloud_cover_low: Option<i32>,
+    pub temperature_model: Option<f32>,
+    pub sea_level_pressure: Option<i32>,
+    pub freezing_level: Option<i32>,
+    pub snow_line: Option<i32>,
+    pub cloud_cover_total: Option<i32>,
+    pub precipitation_amount: Option<f32>,
+    pub precipitation_amount_1h: Option<f32>,
+}
+
+/// Wave forecast as stored in the `wave_forecasts` table
+#[derive(sqlx::FromRow, Debug, Clone, PartialEq)]
+pub struct WaveForecastRecord {
+    pub id_spot: IdSpot,
+    pub id_model: IdModel,
+    pub forecast_from: NaiveDateTime,
+    pub forecast_for: NaiveDateTime,
+    pub wave_height: Option<f32>,
+    pub wave_period: Option<f32>,
+    pub wave_direction: Option<i32>,
+    pub wind_wave_height: Option<f32>,
+    pub wind_wave_period: Option<f32>,
+    pub wind_wave_direction: Option<i32>,
+    pub swell1_height: Option<f32>,
+    pub swell1_period: Option<f32>,
+    pub swell1_direction: Option<i32>,
+    pub swell2_height: Option<f32>,
+    pub swell2_period: Option<f32>,
+    pub swell2_direction: Option<i32>,
+}
+
+/// Station reading as stored in the `station_readings` table along with a spot the station
+/// observes, time is in UTC
+#[derive(sqlx::FromRow, Debug, Clone, PartialEq)]
+pub struct StationReadingRecord {
+    pub id_spot: IdSpot,
+    pub id_station: IdStation,
+    pub avg_minutes: i32,
+    pub time: NaiveDateTime,
+    pub wind_speed_avg: Option<f32>,
+    pub wind_max: Option<f32>,
+    pub wind_min: Option<f32>,
+    pub gustiness: Option<f32>,
+    pub wind_direction: Option<i32>,
+    pub temperature: Option<f32>,
+    pub relative_humidity: Option<i32>,
+    pub mean_sea_level_pressure: Option<f32>,
+}
+
+/// Keeps the spot to station mapping across restarts, readings may be ingested before
+/// the mapping is sent again
+const SPOT_STATIONS_PATH: &str = "spot_stations.parquet";
+
+/// Writes forecasts and station readings as Parquet files, using the columns of
+/// the postgresql tables. Forecasts are partitioned by spot and day of the model
+/// run, station readings by spot and day of the reading.
+#[derive(Clone)]
+pub struct ParquetRepository {
+    directory: PathBuf,
+    /// Spots observed by each station, readings are written under every spot of their station
+    spot_stations: Arc<RwLock<BTreeMap<IdStation, BTreeSet<IdSpot>>>>,
+}
+
+impl ParquetRepository {
+    pub fn new(config: &ParquetConfig) -> Self {
+        Self::from_directory(config.directory.clone())
+    }
+
+    /// Loads the spot to station mapping persisted in the directory before
+    pub fn from_directory(directory: PathBuf) -> Self {
+        let spot_stations = match read_spot_stations(&directory.join(SPOT_STATIONS_PATH)) {
+            Ok(spot_stations) => spot_stations_by_station(&spot_stations),
+            Err(err) => {
+                tracing::warn!("unable to load spot to station mapping {}", err);
+                BTreeMap::new()
+            }
+        };
+
+        Self {
+            directory,
+            spot_stations: Arc::new(RwLock::new(spot_stations)),
+        }
+    }
+
+    /// Writes a file per spot, model and run, returns the number of written files
+    pub async fn write_forecasts(
+        &self,
+        records: Vec<ForecastRecord>,
+    ) -> Result<usize, IngestError> {
+        let mut partitions: BTreeMap<(IdSpot, IdModel, NaiveDateTime), Vec<ForecastRecord>> =
+            BTreeMap::new();
+        for record in records {
+            partitions
+                .entry((record.id_spot, record.id_model, record.forecast_from))
+                .or_default()
+                .push(record);
+        }
+
+        let files = partitions
+            .into_iter()
+            .map(|((id_spot, id_model, forecast_from), records)| {
+                let path = self.directory.join(forecast_path(
+                    "forecasts",
+                    id_spot,
+                    id_model,
+                    forecast_from,
+                ));
+                (path, records)
+            })
+            .collect();
+
+        self.write_files(files, |_, records| forecasts_batch(&records))
+            .await
+    }
+
+    /// Writes a file per spot, wave model and run, returns the number of written files
+    pub async fn write_wave_forecasts(
+        &self,
+        records: Vec<WaveForecastRecord>,
+    ) -> Result<usize, IngestError> {
+        let mut partitions: BTreeMap<(IdSpot, IdModel, NaiveDateTime), Vec<WaveForecastRecord>> =
+            BTreeMap::new();
+        for record in records {
+            partitions
+                .entry((record.id_spot, record.id_model, record.forecast_from))
+                .or_default()
+                .push(record);
+        }
+
+        let files = partitions
+            .into_iter()
+            .map(|((id_spot, id_model, forecast_from), records)| {
+                let path = self.directory.join(forecast_path(
+                    "wave_forecasts",
+                    id_spot,
+                    id_model,
+                    forecast_from,
+                ));
+                (path, records)
+            })
+            .collect();
+
+        self.write_files(files, |_, records| wave_forecasts_batch(&records))
+            .await
+    }
+
+    /// Writes a file per spot, day, station and averaging window, returns the number of
+    /// written files. Readings already stored in a file are kept, unless written again.
+    pub async fn write_station_readings(
+        &self,
+        records: Vec<StationReadingRecord>,
+    ) -> Result<usize, IngestError> {
+        let mut partitions: BTreeMap<
+            (IdSpot, NaiveDate, IdStation, i32),
+            Vec<StationReadingRecord>,
+        > = BTreeMap::new();
+        for record in records {
+            partitions
+                .entry((
+                    record.id_spot,
+                    record.time.date(),
+                    record.id_station,
+                    record.avg_minutes,
+                ))
+                .or_default()
+                .push(record);
+        }
+
+        let files = partitions
+            .into_iter()
+            .map(|((id_spot, date, id_station, avg_minutes), records)| {
+                let path = self.directory.join(station_readings_path(
+                    id_spot,
+                    date,
+                    id_station,
+                    avg_minutes,
+                ));
+                (path, records)
+            })
+            .collect();
+
+        self.write_files(files, |path, records| {
+            let stored = read_station_readings(path)?;
+            station_readings_batch(&merge_station_readings(stored, records))
+        })
+        .await
+    }
+
+    async fn write_files<T, F>(
+        &self,
+        files: Vec<(PathBuf, Vec<T>)>,
+        to_batch: F,
+    ) -> Result<usize, IngestError>
+    where
+        T: Send + 'static,
+        F: Fn(&Path, Vec<T>) -> Result<RecordBatch, ParquetError> + Send + 'static,
+    {
+        tokio::task::spawn_blocking(move || {
+            let written = files.len();
+            for (path, records) in files {
+                let batch = to_batch(&path, records)?;
+                let rows = batch.num_rows();
+                write_parquet(&path, batch)?;
+                tracing::debug!(
+                    path = %path.display(),
+                    rows,
+                    "sucessfuly written parquet file"
+                );
+            }
+            Ok::<_, ParquetError>(written)
+        })
+        .await
+        .map_err(anyhow::Error::from)?
+        .map_err(|err| IngestError::FileStorageError(err.into()))
+    }
+}
+
+fn forecast_path(
+    kind: &str,
+    id_spot: IdSpot,
+    id_model: IdModel,
+    forecast_from: NaiveDateTime,
+) -> PathBuf {
+    PathBuf::from(format!(
+        "{}/spot={}/date={}/model={}_{}.parquet",
+        kind,
+        id_spot,
+        forecast_from.format("%Y-%m-%d"),
+        id_model,
+        forecast_from.format("%Y%m%dT%H%M%S"),
+    ))
+}
+
+fn station_readings_path(
+    id_spot: IdSpot,
+    date: NaiveDate,
+    id_station: IdStation,
+    avg_minutes: i32,
+) -> PathBuf {
+    PathBuf::from(format!(
+        "station_readings/spot={}/date={}/station={}_{}.parquet",
+        id_spot,
+        date.format("%Y-%m-%d"),
+        id_station,
+        avg_minutes,
+    ))
+}
+
+/// Readings stored in a file written before, none when it does not exist yet
+fn read_station_readings(path: &Path) -> Result<Vec<StationReadingRecord>, ParquetError> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?.build()?;
+    let mut records = Vec::new();
+    for batch in reader {
+        let batch = batch?;
+        if batch.schema() != station_reading_schema() {
+            return Err(ParquetError::General(format!(
+                "{} does not have the station readings schema",
+                path.display()
+            )));
+        }
+        records.extend(station_readings_from_batch(&batch));
+    }
+
+    Ok(records)
+}
+
+/// Mapping persisted before, none when it does not exist yet
+fn read_spot_stations(path: &Path) -> Result<Vec<SpotStation>, ParquetError> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?.build()?;
+    let mut spot_stations = Vec::new();
+    for batch in reader {
+        let batch = batch?;
+        if batch.schema() != spot_station_schema() {
+            return Err(ParquetError::General(format!(
+                "{} does not have the spot stations schema",
+                path.display()
+            )));
+        }
+        spot_stations.extend(spot_stations_from_batch(&batch));
+    }
+
+    Ok(spot_stations)
+}
+
+/// Readings repeating the time of a stored one replace it, the result is ordered by time
+fn merge_station_readings(
+    stored: Vec<StationReadingRecord>,
+    records: Vec<StationReadingRecord>,
+) -> Vec<StationReadingRecord> {
+    stored
+        .into_iter()
+        .chain(records)
+        .map(|record| ((record.id_station, record.avg_minutes, record.time), record))
+        .collect::<BTreeMap<_, _>>()
+        .into_values()
+        .collect()
+}
+
+fn timestamp_type() -> DataType {
+    DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+}
+
+pub fn forecast_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id_spot", DataType::Int32, false),
+        Field::new("id_model", DataType::Int32, false),
+        Field::new("forecast_from", timestamp_type(), false),
+        Field::new("forecast_for", timestamp_type(), false),
+        Field::new("wave", DataType::Boolean, true),
+        Field::new("gust", DataType::Float32, true),
+        Field::new("wind_speed", DataType::Float32, true),
+        Field::new("wind_direction", DataType::Int32, true),
+        Field::new("temperature", DataType::Float32, true),
+        Field::new("relative_humidity", DataType::Int32, true),
+        Field::new("precipitation", DataType::Int32, true),
+        Field::new("cloud_cover_high", DataType::Int32, true),
+        Field::new("cloud_cover_mid", DataType::Int32, true),
+        Field::new("cloud_cover_low", DataType::Int32, true),
+        Field::new("temperature_model", DataType::Float32, true),
+        Field::new("sea_level_pressure", DataType::Int32, true),
+        Field::new("freezing_level", DataType::Int32, true),
+        Field::new("snow_line", DataType::Int32, true),
+        Field::new("cloud_cover_total", DataType::Int32, true),
+        Field::new("precipitation_amount", DataType::Float32, true),
+        Field::new("precipitation_amount_1h", DataType::Float32, true),
+    ]))
+}
+
+pub fn wave_forecast_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id_spot", DataType::Int32, false),
+        Field::new("id_model", DataType::Int32, false),
+        Field::new("forecast_from", timestamp_type(), false),
+        Field::new("forecast_for", timestamp_type(), false),
+        Field::new("wave_height", DataType::Float32, true),
+        Field::new("wave_period", DataType::Float32, true),
+        Field::new("wave_direction", DataType::Int32, true),
+        Field::new("wind_wave_height", DataType::Float32, true),
+        Field::new("wind_wave_period", DataType::Float32, true),
+        Field::new("wind_wave_direction", DataType::Int32, true),
+        Field::new("swell1_height", DataType::Float32, true),
+        Field::new("swell1_period", DataType::Float32, true),
+        Field::new("swell1_direction", DataType::Int32, true),
+        Field::new("swell2_height", DataType::Float32, true),
+        Field::new("swell2_period", DataType::Float32, true),
+        Field::new("swell2_direction", DataType::Int32, true),
+    ]))
+}
+
+pub fn station_reading_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id_spot", DataType::Int32, false),
+        Field::new("id_station", DataType::Int32, false),
+        Field::new("avg_minutes", DataType::Int32, false),
+        Field::new("time", timestamp_type(), false),
+        Field::new("wind_speed_avg", DataType::Float32, true),
+        Field::new("wind_max", DataType::Float32, true),
+        Field::new("wind_min", DataType::Float32, true),
+        Field::new("gustiness", DataType::Float32, true),
+        Field::new("wind_direction", DataType::Int32, true),
+        Field::new("temperature", DataType::Float32, true),
+        Field::new("relative_humidity", DataType::Int32, true),
+        Field::new("mean_sea_level_pressure", DataType::Float32, true),
+    ]))
+}
+
+fn spot_station_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id_spot", DataType::Int32, false),
+        Field::new("id_station", DataType::Int32, false),
+        Field::new("avg_minutes", DataType::Int32, false),
+    ]))
+}
+
+fn timestamps<T>(records: &[T], time: impl Fn(&T) -> NaiveDateTime) -> ArrayRef {
+    Arc::new(
+        TimestampMicrosecondArray::from_iter_values(
+            records.iter().map(|r| time(r).and_utc().timestamp_micros()),
+        )
+        .with_timezone("UTC"),
+    )
+}
+
+fn forecasts_batch(records: &[ForecastRecord]) -> Result<RecordBatch, ParquetError> {
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int32Array::from_iter_values(
+            records.iter().map(|r| r.id_spot),
+        )),
+        Arc::new(Int32Array::from_iter_values(
+            records.iter().map(|r| r.id_model),
+        )),
+        timestamps(records, |r| r.forecast_from),
+        timestamps(records, |r| r.forecast_for),
+        Arc::new(BooleanArray::from_iter(records.iter().map(|r| r.wave))),
+        Arc::new(Float32Array::from_iter(records.iter().map(|r| r.gust))),
+        Arc::new(Float32Array::from_iter(
+            records.iter().map(|r| r.wind_speed),
+        )),
+        Arc::new(Int32Array::from_iter(
+            records.iter().map(|r| r.wind_direction),
+        )),
+        Arc::new(Float32Array::from_iter(
+            records.iter().map(|r| r.temperature),
+        )),
+        Arc::new(Int32Array::from_iter(
+            records.iter().map(|r| r.relative_humidity),
+        )),
+        Arc::new(Int32Array::from_iter(
+            records.iter().map(|r| r.precipitation),
+        )),
+        Arc::new(Int32Array::from_iter(
+            records.iter().map(|r| r.cloud_cover_high),
+        )),
+        Arc::new(Int32Array::from_iter(
+            records.iter().map(|r| r.cloud_cover_mid),
+        )),
+        Arc::new(Int32Array::from_iter(
+            records.iter().map(|r| r.cloud_cover_low),
+        )),
+        Arc::new(Float32Array::from_iter(
+            records.iter().map(|r| r.temperature_model),
+        )),
+        Arc::new(Int32Array::from_iter(
+            records.iter().map(|r| r.sea_level_pressure),
+        )),
+        Arc::new(Int32Array::from_iter(
+            records.iter().map(|r| r.freezing_level),
+        )),
+        Arc::new(Int32Array::from_iter(records.iter().map(|r| r.snow_line))),
+        Arc::new(Int32Array::from_iter(
+            records.iter().map(|r| r.cloud_cover_total),
+        )),
+        Arc::new(Float32Array::from_iter(
+            records.iter().map(|r| r.precipitation_amount),
+        )),
+        Arc::new(Float32Array::from_iter(
+            records.iter().map(|r| r.precipitation_amount_1h),
+        )),
+    ];
+
+    Ok(RecordBatch::try_new(forecast_schema(), columns)?)
+}
+
+fn wave_forecasts_batch(records: &[WaveForecastRecord]) -> Result<RecordBatch, ParquetError> {
+    let floats = |value: fn(&WaveForecastRecord) -> Option<f32>| -> ArrayRef {
+        Arc::new(Float32Array::from_iter(records.iter().map(value)))
+    };
+    let ints = |value: fn(&WaveForecastRecord) -> Option<i32>| -> ArrayRef {
+        Arc::new(Int32Array::from_iter(records.iter().map(value)))
+    };
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int32Array::from_iter_values(
+            records.iter().map(|r| r.id_spot),
+        )),
+        Arc::new(Int32Array::from_iter_values(
+            records.iter().map(|r| r.id_model),
+        )),
+        timestamps(records, |r| r.forecast_from),
+        timestamps(records, |r| r.forecast_for),
+        floats(|r| r.wave_height),
+        floats(|r| r.wave_period),
+        ints(|r| r.wave_direction),
+        floats(|r| r.wind_wave_height),
+        floats(|r| r.wind_wave_period),
+        ints(|r| r.wind_wave_direction),
+        floats(|r| r.swell1_height),
+        floats(|r| r.swell1_period),
+        ints(|r| r.swell1_direction),
+        floats(|r| r.swell2_height),
+        floats(|r| r.swell2_period),
+        ints(|r| r.swell2_direction),
+    ];
+
+    Ok(RecordBatch::try_new(wave_forecast_schema(), columns)?)
+}
+
+fn station_readings_batch(records: &[StationReadingRecord]) -> Result<RecordBatch, ParquetError> {
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int32Array::from_iter_values(
+            records.iter().map(|r| r.id_spot),
+        )),
+        Arc::new(Int32Array::from_iter_values(
+            records.iter().map(|r| r.id_station),
+        )),
+        Arc::new(Int32Array::from_iter_values(
+            records.iter().map(|r| r.avg_minutes),
+        )),
+        timestamps(records, |r| r.time),
+        Arc::new(Float32Array::from_iter(
+            records.iter().map(|r| r.wind_speed_avg),
+        )),
+        Arc::new(Float32Array::from_iter(records.iter().map(|r| r.wind_max))),
+        Arc::new(Float32Array::from_iter(records.iter().map(|r| r.wind_min))),
+        Arc::new(Float32Array::from_iter(records.iter().map(|r| r.gustiness))),
+        Arc::new(Int32Array::from_iter(
+            records.iter().map(|r| r.wind_direction),
+        )),
+        Arc::new(Float32Array::from_iter(
+            records.iter().map(|r| r.temperature),
+        )),
+        Arc::new(Int32Array::from_iter(
+            records.iter().map(|r| r.relative_humidity),
+        )),
+        Arc::new(Float32Array::from_iter(
+            records.iter().map(|r| r.mean_sea_level_pressure),
+        )),
+    ];
+
+    Ok(RecordBatch::try_new(station_reading_schema(), columns)?)
+}
+
+fn spot_stations_batch(spot_stations: &[SpotStation]) -> Result<RecordBatch, ParquetError> {
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int32Array::from_iter_values(
+            spot_stations.iter().map(|s| s.id_spot),
+        )),
+        Arc::new(Int32Array::from_iter_values(
+            spot_stations.iter().map(|s| s.id_station),
+        )),
+        Arc::new(Int32Array::from_iter_values(
+            spot_stations.iter().map(|s| s.avg_minutes as i32),
+        )),
+    ];
+
+    Ok(RecordBatch::try_new(spot_station_schema(), columns)?)
+}
+
+/// Expects the columns of `spot_station_schema`
+fn spot_stations_from_batch(batch: &RecordBatch) -> Vec<SpotStation> {
+    let column = |i: usize| {
+        batch
+            .column(i)
+            .as_primitive::<Int32Type>()
+            .values()
+            .to_vec()
+    };
+    let id_spot = column(0);
+    let id_station = column(1);
+    let avg_minutes = column(2);
+
+    (0..batch.num_rows())
+        .map(|i| SpotStation {
+            id_spot: id_spot[i],
+            id_station: id_station[i],
+            avg_minutes: avg_minutes[i] as u32,
+        })
+        .collect()
+}
+
+/// Expects the columns of `station_reading_schema`
+fn station_readings_from_batch(batch: &RecordBatch) -> Vec<StationReadingRecord> {
+    fn values<T: ArrowPrimitiveType>(batch: &RecordBatch, column: usize) -> Vec<Option<T::Native>> {
+        batch.column(column).as_primitive::<T>().iter().collect()
+    }
+
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    let id_spot = values::<Int32Type>(batch, 0);
+    let id_station = values::<Int32Type>(batch, 1);
+    let avg_minutes = values::<Int32Type>(batch, 2);
+    let time = values::<TimestampMicrosecondType>(batch, 3);
+    let wind_speed_avg = values::<Float32Type>(batch, 4);
+    let wind_max = values::<Float32Type>(batch, 5);
+    let wind_min = values::<Float32Type>(batch, 6);
+    let gustiness = values::<Float32Type>(batch, 7);
+    let wind_direction = values::<Int32Type>(batch, 8);
+    let temperature = values::<Float32Type>(batch, 9);
+    let relative_humidity = values::<Int32Type>(batch, 10);
+    let mean_sea_level_pressure = values::<Float32Type>(batch, 11);
+
+    (0..batch.num_rows())
+        .map(|i| StationReadingRecord {
+            id_spot: id_spot[i].unwrap_or_default(),
+            id_station: id_station[i].unwrap_or_default(),
+            avg_minutes: avg_minutes[i].unwrap_or_default(),
+            time: epoch + Duration::microseconds(time[i].unwrap_or_default()),
+            wind_speed_avg: wind_speed_avg[i],
+            wind_max: wind_max[i],
+            wind_min: wind_min[i],
+            gustiness: gustiness[i],
+            wind_direction: wind_direction[i],
+            temperature: temperature[i],
+            relative_humidity: relative_humidity[i],
+            mean_sea_level_pressure: mean_sea_level_pressure[i],
+        })
+        .collect()
+}
+
+fn write_parquet(path: &Path, batch: RecordBatch) -> Result<(), ParquetError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(File::create(path)?, batch.schema(), Some(properties))?;
+    writer.write(&batch)?;
+    writer.close()?;
+
+    Ok(())
+}
+
+fn spot_stations_by_station(
+    spot_stations: &[SpotStation],
+) -> BTreeMap<IdStation, BTreeSet<IdSpot>> {
+    let mut spots: BTreeMap<IdStation, BTreeSet<IdSpot>> = BTreeMap::new();
+    for spot_station in spot_stations {
+        spots
+            .entry(spot_station.id_station)
+            .or_default()
+            .insert(spot_station.id_spot);
+    }
+
+    spots
+}
+
+#[async_trait]
+impl DataIngester for ParquetRepository {
+    async fn ingest_forecast(&self, data: IngestMsg) -> Result<IngestCounts, IngestError> {
+        match data {
+            // spots metadata is not part of the exported history
+            IngestMsg::WindguruForecast(WindguruForecast { forecast, .. }) => {
+                let records: Vec<_> = forecast
+                    .forecasts
+                    .iter()
+                    .map(|fcst| ForecastRecord {
+                        id_spot: forecast.id_spot,
+                        id_model: forecast.wgmodel.id_model,
+                        forecast_from: fcst.forecast_from,
+                        forecast_for: fcst.forecast_for,
+                        wave: Some(forecast.wgmodel.wave),
+                        gust: fcst.gust,
+                        wind_speed: fcst.wind_speed,
+                        wind_direction: fcst.wind_direction,
+                        temperature: fcst.temperature,
+                        relative_humidity: fcst.relative_humidity,
+                        precipitation: fcst.precipitation,
+                        cloud_cover_high: fcst.cloud_cover_high,
+                        cloud_cover_mid: fcst.cloud_cover_mid,
+                        cloud_cover_low: fcst.cloud_cover_low,
+                        temperature_model: fcst.temperature_model,
+                        sea_level_pressure: fcst.slp,
+                        freezing_level: fcst.flhgt,
+                        snow_line: fcst.slhgt,
+                        cloud_cover_total: fcst.tcdc,
+                        precipitation_amount: fcst.apcp,
+                        precipitation_amount_1h: fcst.apcp1,
+                    })
+                    .collect();
+
+                let rows = records.len();
+                self.write_forecasts(records).await?;
+                Ok(IngestCounts::written(rows))
+            }
+            IngestMsg::WindguruWaveForecast(WindguruWaveForecast { forecast, .. }) => {
+                let records: Vec<_> = forecast
+                    .forecasts
+                    .iter()
+                    .map(|fcst| WaveForecastRecord {
+                        id_spot: forecast.id_spot,
+                        id_model: forecast.wgmodel.id_model,
+                        forecast_from: fcst.forecast_from,
+                        forecast_for: fcst.forecast_for,
+                        wave_height: fcst.wave_height,
+                        wave_period: fcst.wave_period,
+                        wave_direction: fcst.wave_direction,
+                        wind_wave_height: fcst.wind_wave_height,
+                        wind_wave_period: fcst.wind_wave_period,
+                        wind_wave_direction: fcst.wind_wave_direction,
+                        swell1_height: fcst.swell1_height,
+                        swell1_period: fcst.swell1_period,
+                        swell1_direction: fcst.swell1_direction,
+                        swell2_height: fcst.swell2_height,
+                        swell2_period: fcst.swell2_period,
+                        swell2_direction: fcst.swell2_direction,
+                    })
+                    .collect();
+
+                let rows = records.len();
+                self.write_wave_forecasts(records).await?;
+                Ok(IngestCounts::written(rows))
+            }
+            IngestMsg::WindguruStationReading(
+                id_station,
+                WindguruStationData {
+                    readings,
+                    avg_minutes,
+                    ..
+                },
+            ) => {
+                let id_station = id_station as IdStation;
+                let spots = self
+                    .spot_stations
+                    .read()
+                    .unwrap()
+                    .get(&id_station)
+                    .cloned()
+                    .unwrap_or_default();
+                if spots.is_empty() {
+                    tracing::warn!(
+                        station = id_station,
+                        "station is not mapped to any spot, skipping its readings"
+                    );
+                    return Ok(IngestCounts {
+                        skipped: readings.readings.len() as u64,
+                        ..Default::default()
+                    });
+                }
+
+                let records: Vec<_> = spots
+                    .iter()
+                    .flat_map(|id_spot| {
+                        readings
+                            .readings
+                            .iter()
+                            .map(move |reading| (id_spot, reading))
+                    })
+                    .map(|(id_spot, reading)| StationReadingRecord {
+                        id_spot: *id_spot,
+                        id_station,
+                        avg_minutes: avg_minutes as i32,
+                        time: reading.datetime_local.naive_utc(),
+                        wind_speed_avg: reading.wind_avg,
+                        wind_max: reading.wind_max,
+                        wind_min: reading.wind_min,
+                        gustiness: reading.gustiness,
+                        wind_direction: reading.wind_direction,
+                        temperature: reading.temperature,
+                        relative_humidity: reading.relative_humidity,
+                        mean_sea_level_pressure: reading.mean_sea_level_pressure,
+                    })
+                    .collect();
+
+                let rows = readings.readings.len();
+                self.write_station_readings(records).await?;
+                Ok(IngestCounts::written(rows))
+            }
+            IngestMsg::WindguruSpotStations(spot_stations) => {
+                *self.spot_stations.write().unwrap() = spot_stations_by_station(&spot_stations);
+
+                let path = self.directory.join(SPOT_STATIONS_PATH);
+                self.write_files(vec![(path, spot_stations)], |_, spot_stations| {
+                    spot_stations_batch(&spot_stations)
+                })
+                .await?;
+                Ok(IngestCounts::default())
+            }
+        }
+    }
+
+    /// Files are not indexed by time, so every range is reported as missing
+    async fn station_readings_count(
+        &self,
+        _id_station: IdStation,
+        _avg_minutes: u32,
+        _from: DateTime<Utc>,
+        _to: DateTime<Utc>,
+    ) -> Result<u64, IngestError> {
+        Ok(0)
+    }
+
+    /// Stored runs are not looked up, so every run is fetched
+    async fn ingested_runs(
+        &self,
+        _id_spot: IdSpot,
+    ) -> Result<BTreeMap<IdModel, IngestedRun>, IngestError> {
+        Ok(BTreeMap::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn converts_forecasts_with_stable_schema() {
+        let forecast_from = NaiveDate::from_ymd_opt(2023, 4, 8)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let record = ForecastRecord {
+            id_spot: 36048,
+            id_model: 3,
+            forecast_from,
+            forecast_for: forecast_from + chrono::Duration::hours(6),
+            wave: Some(false),
+            gust: Some(14.5),
+            wind_speed: Some(10.0),
+            wind_direction: Some(270),
+            temperature: None,
+            relative_humidity: None,
+            precipitation: None,
+            cloud_cover_high: None,
+            cloud_cover_mid: None,
+            cloud_cover_low: None,
+            temperature_model: None,
+            sea_level_pressure: Some(1016),
+            freezing_level: None,
+            snow_line: None,
+            cloud_cover_total: None,
+            precipitation_amount: Some(0.4),
+            precipitation_amount_1h: None,
+        };
+
+        let batch = forecasts_batch(&[record]).unwrap();
+
+        assert_eq!(batch.schema(), forecast_schema());
+        assert_eq!(batch.num_rows(), 1);
+        assert_eq!(
+            forecast_path("forecasts", 36048, 3, forecast_from),
+            PathBuf::from("forecasts/spot=36048/date=2023-04-08/model=3_20230408T120000.parquet")
+        );
+    }
+
+    #[tokio::test]
+    async fn merges_station_readings_of_repeated_fetches() {
+        let directory =
+            std::env::temp_dir().join(format!("parquet_repository_{}", std::process::id()));
+        let repository = ParquetRepository::from_directory(directory.clone());
+        let time = NaiveDate::from_ymd_opt(2023, 4, 8)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let reading = |minutes: i64, wind_speed_avg: f32| StationReadingRecord {
+            id_spot: 36048,
+            id_station: 2764,
+            avg_minutes: 5,
+            time: time + chrono::Duration::minutes(minutes),
+            wind_speed_avg: Some(wind_speed_avg),
+            wind_max: None,
+            wind_min: None,
+            gustiness: None,
+            wind_direction: Some(270),
+            temperature: None,
+            relative_humidity: None,
+            mean_sea_level_pressure: None,
+        };
+
+        repository
+            .write_station_readings(vec![reading(0, 10.0), reading(5, 11.0)])
+            .await
+            .unwrap();
+        repository
+            .write_station_readings(vec![reading(5, 12.0), reading(10, 13.0)])
+            .await
+            .unwrap();
+
+        let path = directory.join(station_readings_path(36048, time.date(), 2764, 5));
+        let stored = read_station_readings(&path);
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            path,
+            directory.join("station_readings/spot=36048/date=2023-04-08/station=2764_5.parquet")
+        );
+        assert_eq!(
+            stored.unwrap(),
+            vec![reading(0, 10.0), reading(5, 12.0), reading(10, 13.0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn loads_spot_stations_persisted_before_restart() {
+        let directory =
+            std::env::temp_dir().join(format!("parquet_spot_stations_{}", std::process::id()));
+        let spot_station = |id_spot, id_station| SpotStation {
+            id_spot,
+            id_station,
+            avg_minutes: 5,
+        };
+
+        ParquetRepository::from_directory(directory.clone())
+            .ingest_forecast(IngestMsg::WindguruSpotStations(vec![
+                spot_station(36048, 2764),
+                spot_station(36049, 2764),
+                spot_station(36048, 2765),
+            ]))
+            .await
+            .unwrap();
+        let restarted = ParquetRepository::from_directory(directory.clone());
+        let spot_stations = restarted.spot_stations.read().unwrap().clone();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            spot_stations,
+            BTreeMap::from([
+                (2764, BTreeSet::from([36048, 36049])),
+                (2765, BTreeSet::from([36048])),
+            ])
+        );
+    }
+}
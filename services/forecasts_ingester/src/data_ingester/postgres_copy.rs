@@ -0,0 +1,131 @@
+use std::fmt::Write;
+
+use chrono::NaiveDateTime;
+use sqlx::PgConnection;
+
+/// Value of a column of an ingested row, bound to a statement or written as CSV for COPY
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnValue {
+    Bool(Option<bool>),
+    Int(Option<i32>),
+    Real(Option<f32>),
+    Timestamp(Option<NaiveDateTime>),
+}
+
+macro_rules! column_value {
+    ($($ty:ty => $variant:ident),*) => {$(
+        impl From<$ty> for ColumnValue {
+            fn from(value: $ty) -> Self {
+                ColumnValue::$variant(Some(value))
+            }
+        }
+
+        impl From<Option<$ty>> for ColumnValue {
+            fn from(value: Option<$ty>) -> Self {
+                ColumnValue::$variant(value)
+            }
+        }
+    )*};
+}
+
+column_value!(bool => Bool, i32 => Int, f32 => Real, NaiveDateTime => Timestamp);
+
+impl ColumnValue {
+    /// Missing values are left empty, which COPY reads as NULL in CSV format
+    fn write_csv(&self, out: &mut String) {
+        let _ = match self {
+            ColumnValue::Bool(Some(value)) => out.write_str(if *value { "t" } else { "f" }),
+            ColumnValue::Int(Some(value)) => write!(out, "{value}"),
+            ColumnValue::Real(Some(value)) => write!(out, "{value}"),
+            ColumnValue::Timestamp(Some(value)) => {
+                write!(out, "{}", value.format("%Y-%m-%d %H:%M:%S%.f"))
+            }
+            ColumnValue::Bool(None)
+            | ColumnValue::Int(None)
+            | ColumnValue::Real(None)
+            | ColumnValue::Timestamp(None) => Ok(()),
+        };
+    }
+}
+
+fn csv_rows(rows: &[Vec<ColumnValue>]) -> String {
+    let mut out = String::new();
+
+    for row in rows {
+        for (i, value) in row.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            value.write_csv(&mut out);
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Copies rows into a temporary table shaped like `table`, dropped once the transaction
+/// commits, and returns its name. Rows are sent `batch_rows` at a time.
+pub async fn copy_into_staging(
+    conn: &mut PgConnection,
+    table: &str,
+    columns: &[&str],
+    rows: &[Vec<ColumnValue>],
+    batch_rows: usize,
+) -> Result<String, sqlx::Error> {
+    let staging = format!("{table}_staging");
+
+    sqlx::query(&format!(
+        "CREATE TEMPORARY TABLE {staging} (LIKE {table}) ON COMMIT DROP"
+    ))
+    .execute(&mut *conn)
+    .await?;
+
+    let mut copy = conn
+        .copy_in_raw(&format!(
+            "COPY {staging} ({}) FROM STDIN WITH (FORMAT csv)",
+            columns.join(", ")
+        ))
+        .await?;
+    for chunk in rows.chunks(batch_rows.max(1)) {
+        copy.send(csv_rows(chunk).into_bytes()).await?;
+    }
+    copy.finish().await?;
+
+    Ok(staging)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn write_rows_as_csv() {
+        let time = NaiveDate::from_ymd_opt(2023, 4, 8)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let rows = vec![
+            vec![
+                ColumnValue::from(36048),
+                ColumnValue::from(time),
+                ColumnValue::from(true),
+                ColumnValue::from(Some(12.5_f32)),
+            ],
+            vec![
+                ColumnValue::from(36048),
+                ColumnValue::from(time + chrono::Duration::hours(1)),
+                ColumnValue::from(false),
+                ColumnValue::from(None::<f32>),
+            ],
+        ];
+
+        assert_eq!(
+            csv_rows(&rows),
+            "36048,2023-04-08 12:00:00,t,12.5\n36048,2023-04-08 13:00:00,f,\n"
+        );
+    }
+}
@@ -1,146 +1,667 @@
-use crate::{actors::messages::ingesting::{IngestMsg, WindguruForecast}, types::windguru::station::WindguruStationData};
+use crate::{
+    actors::messages::ingesting::{IngestMsg, WindguruForecast, WindguruWaveForecast},
+    config::{IngestMethod, PostgresqlConfig},
+    types::windguru::{
+        forecast::{IdModel, IdSpot, Spot, WgModel},
+        station::{IdStation, WindguruStationData},
+    },
+};
 
 use super::errors::IngestError;
-use super::DataIngester;
-use anyhow::anyhow;
+use super::postgres_copy::{copy_into_staging, ColumnValue};
+use super::{DataIngester, IngestCounts, IngestedRun};
 
 use async_trait::async_trait;
-use sqlx::{postgres::PgDatabaseError, PgPool, QueryBuilder};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::{PgConnection, PgPool, Postgres, QueryBuilder};
+use std::collections::BTreeMap;
+
+/// Writes ingested data to postgresql, each message is applied in its own transaction
+#[derive(Clone)]
+pub struct PostgresRepository {
+    pool: PgPool,
+    batch_rows: usize,
+    ingest_method: IngestMethod,
+}
+
+impl PostgresRepository {
+    pub fn new(pool: PgPool, config: &PostgresqlConfig) -> Self {
+        Self {
+            pool,
+            batch_rows: config.batch_rows,
+            ingest_method: config.ingest_method,
+        }
+    }
+
+    /// Rows hold values of the `key` columns followed by the other `columns` of the table
+    async fn upsert_rows(
+        &self,
+        conn: &mut PgConnection,
+        table: &str,
+        key: &[&str],
+        columns: &[&str],
+        rows: &[Vec<ColumnValue>],
+    ) -> Result<IngestCounts, sqlx::Error> {
+        let mut counts = IngestCounts::default();
+
+        match self.ingest_method {
+            IngestMethod::Insert => {
+                let rows_per_statement =
+                    rows_per_statement(self.batch_rows, key.len() + columns.len());
+
+                for chunk in rows.chunks(rows_per_statement) {
+                    let mut query_builder = QueryBuilder::new(insert_into(table, key, columns));
+                    query_builder.push_values(chunk, |mut b, row| {
+                        for value in row {
+                            match *value {
+                                ColumnValue::Bool(value) => b.push_bind(value),
+                                ColumnValue::Int(value) => b.push_bind(value),
+                                ColumnValue::Real(value) => b.push_bind(value),
+                                ColumnValue::Timestamp(value) => b.push_bind(value),
+                            };
+                        }
+                    });
+                    query_builder.push(upsert_clause(table, key, columns));
+
+                    counts += upsert(conn, query_builder, chunk.len()).await?;
+                }
+            }
+            IngestMethod::Copy => {
+                let all_columns: Vec<&str> = key.iter().chain(columns).copied().collect();
+                let staging =
+                    copy_into_staging(conn, table, &all_columns, rows, self.batch_rows).await?;
+
+                let inserted: Vec<(bool,)> = sqlx::query_as(&format!(
+                    "{}SELECT {} FROM {staging}{}",
+                    insert_into(table, key, columns),
+                    all_columns.join(", "),
+                    upsert_clause(table, key, columns)
+                ))
+                .fetch_all(&mut *conn)
+                .await?;
+                let inserted: Vec<bool> =
+                    inserted.into_iter().map(|(inserted,)| inserted).collect();
+
+                counts += upsert_counts(rows.len(), &inserted);
+            }
+        }
+
+        Ok(counts)
+    }
+}
 
 #[async_trait]
-impl DataIngester for PgPool {
-    async fn ingest_forecast(&self, data: IngestMsg) -> Result<(), IngestError> {
+impl DataIngester for PostgresRepository {
+    async fn ingest_forecast(&self, data: IngestMsg) -> Result<IngestCounts, IngestError> {
         match data {
-            IngestMsg::WindguruForecast(WindguruForecast { forecast }) => {
-                let mut query_builder = QueryBuilder::new(
-                    r#"INSERT INTO forecasts(
-                        id_spot,
-                        id_model,
-                        forecast_from,
-                        forecast_for,
-                        wave,
-                        gust,
-                        wind_speed,
-                        wind_direction,
-                        temperature,
-                        relative_humidity,
-                        precipitation,
-                        cloud_cover_high,
-                        cloud_cover_mid,
-                        cloud_cover_low
-                    ) "#,
+            IngestMsg::WindguruForecast(WindguruForecast { spot, forecast }) => {
+                // a statement can not upsert the same row twice
+                let mut fcsts: Vec<_> = forecast.forecasts.iter().collect();
+                fcsts.sort_by_key(|fcst| fcst.forecast_for);
+                fcsts.dedup_by_key(|fcst| fcst.forecast_for);
+
+                let rows: Vec<Vec<ColumnValue>> = fcsts
+                    .iter()
+                    .map(|fcst| {
+                        vec![
+                            forecast.id_spot.into(),
+                            forecast.wgmodel.id_model.into(),
+                            fcst.forecast_from.into(),
+                            fcst.forecast_for.into(),
+                            forecast.wgmodel.wave.into(),
+                            fcst.gust.into(),
+                            fcst.wind_speed.into(),
+                            fcst.wind_direction.into(),
+                            fcst.temperature.into(),
+                            fcst.relative_humidity.into(),
+                            fcst.precipitation.into(),
+                            fcst.cloud_cover_high.into(),
+                            fcst.cloud_cover_mid.into(),
+                            fcst.cloud_cover_low.into(),
+                            fcst.temperature_model.into(),
+                            fcst.slp.into(),
+                            fcst.flhgt.into(),
+                            fcst.slhgt.into(),
+                            fcst.tcdc.into(),
+                            fcst.apcp.into(),
+                            fcst.apcp1.into(),
+                        ]
+                    })
+                    .collect();
+
+                let mut tx = self.pool.begin().await?;
+                upsert_spot(&mut tx, &spot).await?;
+                upsert_model_run(&mut tx, &forecast.wgmodel, forecast.update_last).await?;
+
+                let mut counts = IngestCounts::duplicates(forecast.forecasts.len(), fcsts.len());
+                counts += self
+                    .upsert_rows(&mut tx, "forecasts", FORECAST_KEY, FORECAST_COLUMNS, &rows)
+                    .await?;
+                record_spot_model_run(
+                    &mut tx,
+                    forecast.id_spot,
+                    &forecast.wgmodel,
+                    forecast.update_last,
+                )
+                .await?;
+
+                tx.commit().await?;
+
+                tracing::debug!(
+                    inserted = counts.inserted,
+                    updated = counts.updated,
+                    skipped = counts.skipped,
+                    "sucessfuly upserted forecasts to postgres storage"
                 );
-                query_builder.push_values(Vec::clone(&forecast.forecasts), |mut b, fcst| {
-                    b.push_bind(forecast.id_spot)
-                        .push_bind(forecast.wgmodel.id_model)
-                        .push_bind(fcst.forecast_from)
-                        .push_bind(fcst.forecast_for)
-                        .push_bind(forecast.wgmodel.wave)
-                        .push_bind(fcst.gust)
-                        .push_bind(fcst.wind_speed)
-                        .push_bind(fcst.wind_direction)
-                        .push_bind(fcst.temperature)
-                        .push_bind(fcst.relative_humidity)
-                        .push_bind(fcst.precipitation)
-                        .push_bind(fcst.cloud_cover_high)
-                        .push_bind(fcst.cloud_cover_mid)
-                        .push_bind(fcst.cloud_cover_low);
-                });
+                Ok(counts)
+            }
+            IngestMsg::WindguruWaveForecast(WindguruWaveForecast { spot, forecast }) => {
+                let mut fcsts: Vec<_> = forecast.forecasts.iter().collect();
+                fcsts.sort_by_key(|fcst| fcst.forecast_for);
+                fcsts.dedup_by_key(|fcst| fcst.forecast_for);
+
+                let rows: Vec<Vec<ColumnValue>> = fcsts
+                    .iter()
+                    .map(|fcst| {
+                        vec![
+                            forecast.id_spot.into(),
+                            forecast.wgmodel.id_model.into(),
+                            fcst.forecast_from.into(),
+                            fcst.forecast_for.into(),
+                            fcst.wave_height.into(),
+                            fcst.wave_period.into(),
+                            fcst.wave_direction.into(),
+                            fcst.wind_wave_height.into(),
+                            fcst.wind_wave_period.into(),
+                            fcst.wind_wave_direction.into(),
+                            fcst.swell1_height.into(),
+                            fcst.swell1_period.into(),
+                            fcst.swell1_direction.into(),
+                            fcst.swell2_height.into(),
+                            fcst.swell2_period.into(),
+                            fcst.swell2_direction.into(),
+                        ]
+                    })
+                    .collect();
+
+                let mut tx = self.pool.begin().await?;
+                upsert_spot(&mut tx, &spot).await?;
+                upsert_model_run(&mut tx, &forecast.wgmodel, forecast.update_last).await?;
 
-                sqlx::query(
-                    "INSERT INTO models (id, identifier, name) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+                let mut counts = IngestCounts::duplicates(forecast.forecasts.len(), fcsts.len());
+                counts += self
+                    .upsert_rows(
+                        &mut tx,
+                        "wave_forecasts",
+                        FORECAST_KEY,
+                        WAVE_FORECAST_COLUMNS,
+                        &rows,
+                    )
+                    .await?;
+                record_spot_model_run(
+                    &mut tx,
+                    forecast.id_spot,
+                    &forecast.wgmodel,
+                    forecast.update_last,
                 )
-                .bind(forecast.wgmodel.id_model)
-                .bind(forecast.wgmodel.model)
-                .bind(forecast.wgmodel.model_name)
-                .execute(self)
                 .await?;
 
-                match query_builder.build().execute(self).await {
-                    Ok(rows_affected) => {
-                        tracing::debug!(
-                            rows_affected = rows_affected.rows_affected(),
-                            "sucessfuly inserted data to postgres storage"
-                        );
-                        Ok(())
-                    }
-                    Err(error) => handle_pg_errors(error),
-                }
+                tx.commit().await?;
+
+                tracing::debug!(
+                    inserted = counts.inserted,
+                    updated = counts.updated,
+                    skipped = counts.skipped,
+                    "sucessfuly upserted wave forecasts to postgres storage"
+                );
+                Ok(counts)
             }
-            IngestMsg::WindguruStationReading(id_station, WindguruStationData{ readings, datetime_start_utc, datetime_end_utc })  => {
-                let mut query_builder = QueryBuilder::new(
-                    r#"INSERT INTO station_readings(
-                        id_spot,
-                        time,
-                        -- sunrise, to be added later on
-                        -- sunset, to be added later on
-                        wind_speed_avg,
-                        wind_max,
-                        wind_direction,
-                        temperature
-                    ) "#,
+            IngestMsg::WindguruStationReading(
+                id_station,
+                WindguruStationData {
+                    readings,
+                    avg_minutes,
+                    ..
+                },
+            ) => {
+                if readings.readings.is_empty() {
+                    return Ok(IngestCounts::default());
+                }
+
+                let mut station_readings: Vec<_> = readings.readings.iter().collect();
+                station_readings.sort_by_key(|reading| reading.datetime_local);
+                station_readings.dedup_by_key(|reading| reading.datetime_local);
+
+                let rows: Vec<Vec<ColumnValue>> = station_readings
+                    .iter()
+                    .map(|reading| {
+                        vec![
+                            (id_station as IdStation).into(),
+                            (avg_minutes as i32).into(),
+                            reading.datetime_local.naive_utc().into(),
+                            reading.wind_avg.into(),
+                            reading.wind_max.into(),
+                            reading.wind_min.into(),
+                            reading.gustiness.into(),
+                            reading.wind_direction.into(),
+                            reading.temperature.into(),
+                            reading.relative_humidity.into(),
+                            reading.mean_sea_level_pressure.into(),
+                        ]
+                    })
+                    .collect();
+
+                let mut tx = self.pool.begin().await?;
+                sqlx::query("INSERT INTO stations (id) VALUES ($1) ON CONFLICT DO NOTHING")
+                    .bind(id_station)
+                    .execute(&mut tx)
+                    .await?;
+
+                let mut counts =
+                    IngestCounts::duplicates(readings.readings.len(), station_readings.len());
+                counts += self
+                    .upsert_rows(
+                        &mut tx,
+                        "station_readings",
+                        STATION_READING_KEY,
+                        STATION_READING_COLUMNS,
+                        &rows,
+                    )
+                    .await?;
+
+                tx.commit().await?;
+
+                tracing::debug!(
+                    inserted = counts.inserted,
+                    updated = counts.updated,
+                    skipped = counts.skipped,
+                    "sucessfuly upserted station readings to postgres storage"
                 );
+                Ok(counts)
+            }
+            IngestMsg::WindguruSpotStations(spot_stations) => {
+                // an empty watch list is not trusted to remove every mapping
+                if spot_stations.is_empty() {
+                    tracing::warn!("no spot to station mappings given, keeping stored ones");
+                    return Ok(IngestCounts::default());
+                }
 
-                query_builder.push_values(readings.readings, |mut b, reading| {
-                    b.push_bind(id_station)
-                        .push_bind(reading.datetime_local)
-                        .push_bind(reading.wind_avg)
-                        .push_bind(reading.wind_max)
-                        .push_bind(reading.wind_direction)
-                        .push_bind(reading.temperature);
-                });
+                let mut tx = self.pool.begin().await?;
 
-                match query_builder.build().execute(self).await {
-                    Ok(rows_affected) => {
-                        tracing::debug!(
-                            rows_affected = rows_affected.rows_affected(),
-                            "sucessfuly inserted data to postgres storage"
-                        );
-                        Ok(())
-                    }
-                    Err(error) => {
-                        handle_pg_errors(error)
-                    }
+                // stations dropped from the watch list no longer observe their spots
+                let (spots, stations): (Vec<IdSpot>, Vec<IdStation>) = spot_stations
+                    .iter()
+                    .map(|spot_station| (spot_station.id_spot, spot_station.id_station))
+                    .unzip();
+                let removed = sqlx::query(
+                    r#"DELETE FROM spot_stations
+                    WHERE (id_spot, id_station) NOT IN (
+                        SELECT * FROM UNNEST($1::INT[], $2::INT[])
+                    )"#,
+                )
+                .bind(&spots)
+                .bind(&stations)
+                .execute(&mut tx)
+                .await?
+                .rows_affected();
+                if removed > 0 {
+                    tracing::info!(removed, "removed spot to station mappings");
                 }
-            },
-            IngestMsg::WindguruSpot(spot) => {
-                sqlx::query("INSERT INTO spots (id, name, country, models, gmt_hour_offset) VALUES ($1, $2, $3, $4, $5) ON CONFLICT (id) DO NOTHING")
-                    .bind(spot.id)
-                    .bind(spot.name)
-                    .bind(spot.country)
-                    .bind(spot.models)
-                    .bind(spot.gmt_hour_offset)
-                    .execute(self).await?;
-                Ok(())
-            },
-            other => {
-                tracing::warn!("Unimplemented message received {other}");
-                Err(anyhow!("Unimplemented message received {other}").into())
+
+                let mut stations_query = QueryBuilder::new("INSERT INTO stations (id) ");
+                stations_query.push_values(&spot_stations, |mut b, spot_station| {
+                    b.push_bind(spot_station.id_station);
+                });
+                stations_query.push(" ON CONFLICT DO NOTHING");
+                stations_query.build().execute(&mut tx).await?;
+
+                let mut mapping_query = QueryBuilder::new(
+                    "INSERT INTO spot_stations (id_spot, id_station, avg_minutes) ",
+                );
+                mapping_query.push_values(&spot_stations, |mut b, spot_station| {
+                    b.push_bind(spot_station.id_spot)
+                        .push_bind(spot_station.id_station)
+                        .push_bind(spot_station.avg_minutes as i32);
+                });
+                mapping_query.push(
+                    " ON CONFLICT (id_spot, id_station) DO UPDATE SET avg_minutes = EXCLUDED.avg_minutes",
+                );
+                mapping_query.build().execute(&mut tx).await?;
+
+                tx.commit().await?;
+
+                Ok(IngestCounts::default())
             }
         }
     }
+
+    async fn station_readings_count(
+        &self,
+        id_station: IdStation,
+        avg_minutes: u32,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<u64, IngestError> {
+        let (count,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM station_readings WHERE id_station = $1 AND avg_minutes = $2 AND time >= $3 AND time < $4",
+        )
+        .bind(id_station)
+        .bind(avg_minutes as i32)
+        .bind(from.naive_utc())
+        .bind(to.naive_utc())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count as u64)
+    }
+
+    async fn ingested_runs(
+        &self,
+        id_spot: IdSpot,
+    ) -> Result<BTreeMap<IdModel, IngestedRun>, IngestError> {
+        let runs: Vec<(IdModel, NaiveDateTime, String, NaiveDateTime)> = sqlx::query_as(
+            r#"SELECT DISTINCT ON (model_runs.id_model)
+                model_runs.id_model, model_runs.init_time, spot_model_runs.rundef, spot_model_runs.update_last
+            FROM model_runs
+            JOIN spot_model_runs USING (id_model, init_time)
+            WHERE spot_model_runs.id_spot = $1
+            ORDER BY model_runs.id_model, model_runs.init_time DESC"#,
+        )
+        .bind(id_spot)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(runs
+            .into_iter()
+            .map(|(id_model, init_time, rundef, update_last)| {
+                (
+                    id_model,
+                    IngestedRun {
+                        init_time: init_time.and_utc(),
+                        rundef,
+                        update_last,
+                    },
+                )
+            })
+            .collect())
+    }
 }
 
-fn handle_pg_errors(error: sqlx::error::Error) -> Result<(), IngestError> {
-    match error {
-        sqlx::error::Error::Database(db_err) => {
-            match db_err.code() {
-                // 23505 - key is duplicated
-                // in this case it is acceptable behaviour
-                Some(std::borrow::Cow::Borrowed("23505")) => {
-                    let pg_err: Box<PgDatabaseError> = db_err.downcast();
-
-                    tracing::debug!(
-                        table = pg_err.table(),
-                        constraint = pg_err.constraint(),
-                        "data already present details={:?}",
-                        pg_err.detail()
-                    );
-                    Ok(())
-                }
-                _ => Err(db_err.into()),
+/// Records the spot, which forecasts reference, keeping its previous version in spot_changes.
+/// A changed spot is locked when its previous version is copied, so concurrent forecasts of
+/// the spot record the change only once.
+async fn upsert_spot(conn: &mut PgConnection, spot: &Spot) -> Result<(), sqlx::Error> {
+    let changed = sqlx::query(
+        r#"INSERT INTO spot_changes (id_spot, name, country, lat, lon, alt, tzid, gmt_hour_offset, models, valid_from, valid_until)
+        SELECT id, name, country, lat, lon, alt, tzid, gmt_hour_offset, models, updated_at, NOW()
+        FROM spots
+        WHERE id = $1
+            AND (name, country, lat, lon, alt, tzid, gmt_hour_offset, models)
+                IS DISTINCT FROM ($2::VARCHAR, $3::VARCHAR, $4::FLOAT8, $5::FLOAT8, $6::INT, $7::VARCHAR, $8::INT, $9::INT[])
+        FOR UPDATE"#,
+    )
+    .bind(spot.id)
+    .bind(&spot.name)
+    .bind(&spot.country)
+    .bind(spot.lat)
+    .bind(spot.lon)
+    .bind(spot.alt)
+    .bind(&spot.tzid)
+    .bind(spot.gmt_hour_offset)
+    .bind(&spot.models)
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    sqlx::query(
+        r#"INSERT INTO spots (id, name, country, lat, lon, alt, tzid, gmt_hour_offset, models)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (id) DO UPDATE SET
+            name = EXCLUDED.name,
+            country = EXCLUDED.country,
+            lat = EXCLUDED.lat,
+            lon = EXCLUDED.lon,
+            alt = EXCLUDED.alt,
+            tzid = EXCLUDED.tzid,
+            gmt_hour_offset = EXCLUDED.gmt_hour_offset,
+            models = EXCLUDED.models,
+            updated_at = NOW()
+        WHERE (spots.name, spots.country, spots.lat, spots.lon, spots.alt, spots.tzid, spots.gmt_hour_offset, spots.models)
+            IS DISTINCT FROM (EXCLUDED.name, EXCLUDED.country, EXCLUDED.lat, EXCLUDED.lon, EXCLUDED.alt, EXCLUDED.tzid, EXCLUDED.gmt_hour_offset, EXCLUDED.models)"#,
+    )
+    .bind(spot.id)
+    .bind(&spot.name)
+    .bind(&spot.country)
+    .bind(spot.lat)
+    .bind(spot.lon)
+    .bind(spot.alt)
+    .bind(&spot.tzid)
+    .bind(spot.gmt_hour_offset)
+    .bind(&spot.models)
+    .execute(&mut *conn)
+    .await?;
+
+    if changed > 0 {
+        tracing::info!(id_spot = spot.id, "spot metadata changed");
+    }
+    Ok(())
+}
+
+/// Records the model and its run, which forecasts reference
+async fn upsert_model_run(
+    conn: &mut PgConnection,
+    wgmodel: &WgModel,
+    update_last: NaiveDateTime,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO models (id, identifier, name, hr_step) VALUES ($1, $2, $3, $4) ON CONFLICT (id) DO UPDATE SET hr_step = EXCLUDED.hr_step",
+    )
+    .bind(wgmodel.id_model)
+    .bind(&wgmodel.model)
+    .bind(&wgmodel.model_name)
+    .bind(wgmodel.hr_step as i32)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        r#"INSERT INTO model_runs (id_model, init_time, rundef, hr_start, hr_end, hr_step, update_last, fetched_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, NOW())
+        ON CONFLICT (id_model, init_time) DO UPDATE SET
+            rundef = EXCLUDED.rundef,
+            hr_start = EXCLUDED.hr_start,
+            hr_end = EXCLUDED.hr_end,
+            hr_step = EXCLUDED.hr_step,
+            update_last = EXCLUDED.update_last,
+            fetched_at = EXCLUDED.fetched_at"#,
+    )
+    .bind(wgmodel.id_model)
+    .bind(wgmodel.initdate.naive_utc())
+    .bind(&wgmodel.rundef)
+    .bind(wgmodel.hr_start as i32)
+    .bind(wgmodel.hr_end as i32)
+    .bind(wgmodel.hr_step as i32)
+    .bind(update_last)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Records that forecasts of the run were stored for the spot, within the transaction
+/// storing them
+async fn record_spot_model_run(
+    conn: &mut PgConnection,
+    id_spot: IdSpot,
+    wgmodel: &WgModel,
+    update_last: NaiveDateTime,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"INSERT INTO spot_model_runs (id_spot, id_model, init_time, rundef, update_last, ingested_at)
+        VALUES ($1, $2, $3, $4, $5, NOW())
+        ON CONFLICT (id_spot, id_model, init_time) DO UPDATE SET
+            rundef = EXCLUDED.rundef,
+            update_last = EXCLUDED.update_last,
+            ingested_at = EXCLUDED.ingested_at"#,
+    )
+    .bind(id_spot)
+    .bind(wgmodel.id_model)
+    .bind(wgmodel.initdate.naive_utc())
+    .bind(&wgmodel.rundef)
+    .bind(update_last)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Postgresql accepts at most that many bind parameters in a single statement
+const MAX_BIND_PARAMS: usize = u16::MAX as usize;
+
+/// Updatable columns of the forecasts table, along with its key they form the inserted row
+const FORECAST_COLUMNS: &[&str] = &[
+    "wave",
+    "gust",
+    "wind_speed",
+    "wind_direction",
+    "temperature",
+    "relative_humidity",
+    "precipitation",
+    "cloud_cover_high",
+    "cloud_cover_mid",
+    "cloud_cover_low",
+    "temperature_model",
+    "sea_level_pressure",
+    "freezing_level",
+    "snow_line",
+    "cloud_cover_total",
+    "precipitation_amount",
+    "precipitation_amount_1h",
+];
+const FORECAST_KEY: &[&str] = &["id_spot", "id_model", "forecast_from", "forecast_for"];
+
+const WAVE_FORECAST_COLUMNS: &[&str] = &[
+    "wave_height",
+    "wave_period",
+    "wave_direction",
+    "wind_wave_height",
+    "wind_wave_period",
+    "wind_wave_direction",
+    "swell1_height",
+    "swell1_period",
+    "swell1_direction",
+    "swell2_height",
+    "swell2_period",
+    "swell2_direction",
+];
+
+// sunrise and sunset are to be added later on
+const STATION_READING_COLUMNS: &[&str] = &[
+    "wind_speed_avg",
+    "wind_max",
+    "wind_min",
+    "gustiness",
+    "wind_direction",
+    "temperature",
+    "relative_humidity",
+    "mean_sea_level_pressure",
+];
+const STATION_READING_KEY: &[&str] = &["id_station", "avg_minutes", "time"];
+
+/// Rows of a table with `columns` columns written by a single statement
+pub(crate) fn rows_per_statement(batch_rows: usize, columns: usize) -> usize {
+    batch_rows.min(MAX_BIND_PARAMS / columns).max(1)
+}
+
+/// Values of the inserted rows are given in the order of their key and then the columns
+fn insert_into(table: &str, key: &[&str], columns: &[&str]) -> String {
+    format!(
+        "INSERT INTO {table}({}, {}) ",
+        key.join(", "),
+        columns.join(", ")
+    )
+}
+
+/// Conflicting rows are updated only when some of their values changed, every written row
+/// is returned with whether it was inserted, the ones left as they were are not returned
+fn upsert_clause(table: &str, key: &[&str], columns: &[&str]) -> String {
+    let set = columns
+        .iter()
+        .map(|column| format!("{column} = EXCLUDED.{column}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let stored = columns
+        .iter()
+        .map(|column| format!("{table}.{column}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let excluded = columns
+        .iter()
+        .map(|column| format!("EXCLUDED.{column}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!(
+        " ON CONFLICT ({}) DO UPDATE SET {set} WHERE ({stored}) IS DISTINCT FROM ({excluded}) RETURNING (xmax = 0) AS inserted",
+        key.join(", ")
+    )
+}
+
+/// Runs an upsert of `rows` rows built with `upsert_clause`
+async fn upsert(
+    conn: &mut PgConnection,
+    mut query_builder: QueryBuilder<'_, Postgres>,
+    rows: usize,
+) -> Result<IngestCounts, sqlx::Error> {
+    let inserted: Vec<(bool,)> = query_builder.build_query_as().fetch_all(conn).await?;
+    let inserted: Vec<bool> = inserted.into_iter().map(|(inserted,)| inserted).collect();
+
+    Ok(upsert_counts(rows, &inserted))
+}
+
+/// `inserted` holds a flag for every row written by an upsert of `rows` rows
+fn upsert_counts(rows: usize, inserted: &[bool]) -> IngestCounts {
+    let new = inserted.iter().filter(|inserted| **inserted).count();
+
+    IngestCounts {
+        inserted: new as u64,
+        updated: (inserted.len() - new) as u64,
+        skipped: (rows - inserted.len()) as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn split_batches_under_bind_limit() {
+        assert_eq!(rows_per_statement(1000, 21), 1000);
+        assert_eq!(rows_per_statement(10_000, 21), 3120);
+        assert_eq!(rows_per_statement(0, 21), 1);
+        assert_eq!(
+            insert_into("station_readings", &["id_station", "time"], &["wind_max"]),
+            "INSERT INTO station_readings(id_station, time, wind_max) "
+        );
+    }
+
+    #[test]
+    fn upsert_only_changed_rows() {
+        assert_eq!(
+            upsert_clause("station_readings", &["id_station", "time"], &["wind_max", "wind_min"]),
+            " ON CONFLICT (id_station, time) DO UPDATE SET wind_max = EXCLUDED.wind_max, wind_min = EXCLUDED.wind_min \
+            WHERE (station_readings.wind_max, station_readings.wind_min) IS DISTINCT FROM (EXCLUDED.wind_max, EXCLUDED.wind_min) \
+            RETURNING (xmax = 0) AS inserted"
+        );
+
+        assert_eq!(
+            upsert_counts(5, &[true, false, true]),
+            IngestCounts {
+                inserted: 2,
+                updated: 1,
+                skipped: 2,
             }
-        }
-        _ => Err(error.into()),
+        );
     }
 }
@@ -0,0 +1,254 @@
+use crate::{
+    actors::messages::ingesting::{IngestMsg, WindguruForecast, WindguruWaveForecast},
+    config::S3Config,
+    types::windguru::{
+        forecast::{IdModel, IdSpot, Spot, WgModel},
+        station::{IdStation, WindguruStationData, WindguruStationReading},
+    },
+};
+
+use super::errors::IngestError;
+use super::{DataIngester, IngestCounts, IngestedRun};
+
+use async_trait::async_trait;
+use aws_sdk_s3::{
+    config::{Credentials, Region},
+    primitives::ByteStream,
+    Client,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+const JSON_LINES_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Writes ingested data as JSON lines objects partitioned by spot or station, model and date
+#[derive(Clone)]
+pub struct S3Repository {
+    client: Client,
+    bucket: String,
+    prefix: String,
+}
+
+#[derive(Serialize)]
+struct ForecastRow<'a, F> {
+    id_spot: IdSpot,
+    id_model: IdModel,
+    #[serde(flatten)]
+    forecast: &'a F,
+}
+
+#[derive(Serialize)]
+struct StationReadingRow<'a> {
+    id_station: IdStation,
+    avg_minutes: u32,
+    #[serde(flatten)]
+    reading: &'a WindguruStationReading,
+}
+
+impl S3Repository {
+    pub async fn new(config: &S3Config) -> Self {
+        let sdk_config = aws_config::from_env()
+            .region(Region::new(config.region.clone()))
+            .load()
+            .await;
+
+        let mut builder = aws_sdk_s3::config::Builder::from(&sdk_config)
+            .force_path_style(config.force_path_style);
+        if let Some(endpoint_url) = &config.endpoint_url {
+            builder = builder.endpoint_url(endpoint_url);
+        }
+        if let (Some(access_key_id), Some(secret_access_key)) =
+            (&config.access_key_id, &config.secret_access_key)
+        {
+            builder = builder.credentials_provider(Credentials::new(
+                access_key_id,
+                secret_access_key,
+                None,
+                None,
+                "settings",
+            ));
+        }
+
+        Self {
+            client: Client::from_conf(builder.build()),
+            bucket: config.bucket.clone(),
+            prefix: config.prefix.trim_matches('/').to_owned(),
+        }
+    }
+
+    async fn put_object(&self, path: String, body: Vec<u8>) -> Result<(), IngestError> {
+        let key = object_key(&self.prefix, &path);
+
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(&key)
+            .content_type(JSON_LINES_CONTENT_TYPE)
+            .body(ByteStream::from(body))
+            .send()
+            .await
+            .map_err(|err| IngestError::ObjectStorageError(err.into()))?;
+
+        tracing::debug!(bucket = self.bucket, key = key, "sucessfuly written object");
+        Ok(())
+    }
+
+    /// Latest metadata of the spot, rewritten with every forecast of it
+    async fn put_spot(&self, spot: &Spot) -> Result<(), IngestError> {
+        let body = to_json_lines(std::iter::once(spot))?;
+
+        self.put_object(format!("spots/spot={}.jsonl", spot.id), body)
+            .await
+    }
+}
+
+fn object_key(prefix: &str, path: &str) -> String {
+    if prefix.is_empty() {
+        path.to_owned()
+    } else {
+        format!("{prefix}/{path}")
+    }
+}
+
+fn forecast_path(kind: &str, id_spot: IdSpot, wgmodel: &WgModel) -> String {
+    let initdate = wgmodel.initdate;
+
+    format!(
+        "{}/spot={}/model={}/date={}/{}.jsonl",
+        kind,
+        id_spot,
+        wgmodel.id_model,
+        initdate.format("%Y-%m-%d"),
+        initdate.format("%Y%m%dT%H%M%S"),
+    )
+}
+
+fn station_readings_path(
+    id_station: IdStation,
+    avg_minutes: u32,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> String {
+    format!(
+        "station_readings/station={}/date={}/avg_minutes={}_{}_{}.jsonl",
+        id_station,
+        start.format("%Y-%m-%d"),
+        avg_minutes,
+        start.format("%Y%m%dT%H%M%S"),
+        end.format("%Y%m%dT%H%M%S"),
+    )
+}
+
+fn to_json_lines<T: Serialize>(rows: impl Iterator<Item = T>) -> Result<Vec<u8>, IngestError> {
+    let mut body = Vec::new();
+
+    for row in rows {
+        serde_json::to_writer(&mut body, &row).map_err(anyhow::Error::from)?;
+        body.push(b'\n');
+    }
+
+    Ok(body)
+}
+
+#[async_trait]
+impl DataIngester for S3Repository {
+    async fn ingest_forecast(&self, data: IngestMsg) -> Result<IngestCounts, IngestError> {
+        match data {
+            IngestMsg::WindguruForecast(WindguruForecast { spot, forecast }) => {
+                self.put_spot(&spot).await?;
+
+                let body = to_json_lines(forecast.forecasts.iter().map(|fcst| ForecastRow {
+                    id_spot: forecast.id_spot,
+                    id_model: forecast.wgmodel.id_model,
+                    forecast: fcst,
+                }))?;
+
+                let path = forecast_path("forecasts", forecast.id_spot, &forecast.wgmodel);
+                self.put_object(path, body).await?;
+                Ok(IngestCounts::written(forecast.forecasts.len()))
+            }
+            IngestMsg::WindguruWaveForecast(WindguruWaveForecast { spot, forecast }) => {
+                self.put_spot(&spot).await?;
+
+                let body = to_json_lines(forecast.forecasts.iter().map(|fcst| ForecastRow {
+                    id_spot: forecast.id_spot,
+                    id_model: forecast.wgmodel.id_model,
+                    forecast: fcst,
+                }))?;
+
+                let path = forecast_path("wave_forecasts", forecast.id_spot, &forecast.wgmodel);
+                self.put_object(path, body).await?;
+                Ok(IngestCounts::written(forecast.forecasts.len()))
+            }
+            IngestMsg::WindguruStationReading(id_station, data) => {
+                let WindguruStationData {
+                    readings,
+                    datetime_start_utc,
+                    datetime_end_utc,
+                    avg_minutes,
+                } = data;
+
+                let body =
+                    to_json_lines(readings.readings.iter().map(|reading| StationReadingRow {
+                        id_station: id_station as IdStation,
+                        avg_minutes,
+                        reading,
+                    }))?;
+
+                let path = station_readings_path(
+                    id_station as IdStation,
+                    avg_minutes,
+                    datetime_start_utc,
+                    datetime_end_utc,
+                );
+                self.put_object(path, body).await?;
+                Ok(IngestCounts::written(readings.readings.len()))
+            }
+            IngestMsg::WindguruSpotStations(spot_stations) => {
+                let body = to_json_lines(spot_stations.iter())?;
+
+                self.put_object("spot_stations.jsonl".into(), body).await?;
+                Ok(IngestCounts::default())
+            }
+        }
+    }
+
+    /// Objects can not be queried by time, so every range is reported as missing
+    async fn station_readings_count(
+        &self,
+        _id_station: IdStation,
+        _avg_minutes: u32,
+        _from: DateTime<Utc>,
+        _to: DateTime<Utc>,
+    ) -> Result<u64, IngestError> {
+        Ok(0)
+    }
+
+    /// Stored runs are not looked up, so every run is fetched
+    async fn ingested_runs(
+        &self,
+        _id_spot: IdSpot,
+    ) -> Result<BTreeMap<IdModel, IngestedRun>, IngestError> {
+        Ok(BTreeMap::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn partitions_station_readings_by_station_and_date() {
+        let start = Utc.with_ymd_and_hms(2023, 4, 15, 4, 30, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2023, 4, 15, 22, 30, 0).unwrap();
+
+        assert_eq!(
+            object_key("windguru", &station_readings_path(2764, 5, start, end)),
+            "windguru/station_readings/station=2764/date=2023-04-15/avg_minutes=5_20230415T043000_20230415T223000.jsonl"
+        );
+    }
+}
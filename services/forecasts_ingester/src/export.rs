@@ -0,0 +1,117 @@
+use std::path::PathBuf;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use clap::Args;
+use sqlx::PgPool;
+
+use crate::{
+    data_ingester::parquet_repository::{ForecastRecord, StationReadingRecord},
+    types::windguru::forecast::IdSpot,
+};
+
+/// Exports stored forecasts and station readings to partitioned Parquet files
+#[derive(Args, Debug)]
+pub struct ExportParams {
+    /// First day of the range
+    #[arg(long)]
+    pub from: NaiveDate,
+    /// Last day of the range, inclusive
+    #[arg(long)]
+    pub to: NaiveDate,
+    /// Spots to export together with their stations, may be repeated, every spot when omitted
+    #[arg(long = "spot")]
+    pub spots: Vec<IdSpot>,
+    /// Directory the files are written to
+    #[arg(long)]
+    pub output: PathBuf,
+}
+
+impl ExportParams {
+    fn period(&self) -> (NaiveDateTime, NaiveDateTime) {
+        (
+            self.from.and_hms_opt(0, 0, 0).unwrap(),
+            self.to.succ_opt().unwrap().and_hms_opt(0, 0, 0).unwrap(),
+        )
+    }
+}
+
+/// Loads forecasts of model runs initialized within the range
+pub async fn load_forecast_records(
+    pool: &PgPool,
+    params: &ExportParams,
+) -> Result<Vec<ForecastRecord>, sqlx::Error> {
+    let (from, to) = params.period();
+
+    sqlx::query_as(
+        r#"SELECT
+            id_spot,
+            id_model,
+            forecast_from,
+            forecast_for,
+            wave,
+            gust,
+            wind_speed,
+            wind_direction,
+            temperature,
+            relative_humidity,
+            precipitation,
+            cloud_cover_high,
+            cloud_cover_mid,
+            cloud_cover_low,
+            temperature_model,
+            sea_level_pressure,
+            freezing_level,
+            snow_line,
+            cloud_cover_total,
+            precipitation_amount,
+            precipitation_amount_1h
+        FROM forecasts
+        WHERE forecast_from >= $1
+            AND forecast_from < $2
+            AND forecast_for IS NOT NULL
+            AND (CARDINALITY($3::INT[]) = 0 OR id_spot = ANY($3))
+        ORDER BY id_spot, id_model, forecast_from, forecast_for"#,
+    )
+    .bind(from)
+    .bind(to)
+    .bind(&params.spots)
+    .fetch_all(pool)
+    .await
+}
+
+/// Loads readings within the range of stations mapped to the selected spots, or to any
+/// spot when none was selected. Readings of a station observing several spots are loaded
+/// once for each of them.
+pub async fn load_station_reading_records(
+    pool: &PgPool,
+    params: &ExportParams,
+) -> Result<Vec<StationReadingRecord>, sqlx::Error> {
+    let (from, to) = params.period();
+
+    sqlx::query_as(
+        r#"SELECT
+            spot_stations.id_spot,
+            station_readings.id_station,
+            station_readings.avg_minutes,
+            time,
+            wind_speed_avg,
+            wind_max,
+            wind_min,
+            gustiness,
+            wind_direction,
+            temperature,
+            relative_humidity,
+            mean_sea_level_pressure
+        FROM station_readings
+        JOIN spot_stations ON spot_stations.id_station = station_readings.id_station
+        WHERE time >= $1
+            AND time < $2
+            AND (CARDINALITY($3::INT[]) = 0 OR spot_stations.id_spot = ANY($3))
+        ORDER BY spot_stations.id_spot, station_readings.id_station, station_readings.avg_minutes, time"#,
+    )
+    .bind(from)
+    .bind(to)
+    .bind(&params.spots)
+    .fetch_all(pool)
+    .await
+}
@@ -1,7 +1,12 @@
 pub mod actors;
+pub mod analysis;
+pub mod backfill;
 pub mod config;
 pub mod data_fetcher;
 pub mod data_ingester;
+pub mod export;
 pub mod logging;
+pub mod scheduler;
 pub mod state;
 pub mod types;
+pub mod watch_list;
@@ -0,0 +1,322 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Timelike, Utc};
+
+use crate::{
+    config::SchedulerConfig,
+    data_ingester::IngestedRun,
+    types::windguru::forecast::{IdModel, IdSpot},
+};
+
+/// Keeps track of when each forecast model is expected to publish its next run
+pub struct ForecastSchedule {
+    default_run_period: Duration,
+    publish_delay: Duration,
+    retry: Duration,
+    next_fetch: BTreeMap<IdModel, DateTime<Utc>>,
+    runs: BTreeMap<IdModel, ModelRuns>,
+    fallback: DateTime<Utc>,
+}
+
+/// Latest run fetched of a model and the shortest interval seen between its runs
+struct ModelRuns {
+    last_init: DateTime<Utc>,
+    period: Option<Duration>,
+}
+
+impl ForecastSchedule {
+    pub fn new(config: &SchedulerConfig, models: &[IdModel], now: DateTime<Utc>) -> Self {
+        Self {
+            default_run_period: Duration::hours(config.forecast_run_period_hours),
+            publish_delay: Duration::minutes(config.forecast_publish_delay_minutes),
+            retry: Duration::minutes(config.retry_minutes),
+            next_fetch: models.iter().map(|model| (*model, now)).collect(),
+            runs: BTreeMap::new(),
+            fallback: now,
+        }
+    }
+
+    /// Time at which the earliest model is due, or the fallback if no model is known yet
+    pub fn next_due(&self) -> DateTime<Utc> {
+        self.next_fetch
+            .values()
+            .min()
+            .copied()
+            .unwrap_or(self.fallback)
+    }
+
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.next_due() <= now
+    }
+
+    /// Starts tracking models which were not watched before, they are due immediately
+    pub fn watch(&mut self, models: &[IdModel], now: DateTime<Utc>) {
+        for model in models {
+            self.next_fetch.entry(*model).or_insert(now);
+        }
+    }
+
+    pub fn due_models(&self, now: DateTime<Utc>) -> Vec<IdModel> {
+        self.next_fetch
+            .iter()
+            .filter(|(_, next)| **next <= now)
+            .map(|(model, _)| *model)
+            .collect()
+    }
+
+    /// Schedules the next fetch of a model based on the init time of the run just fetched
+    pub fn record_run(&mut self, model: IdModel, initdate: DateTime<Utc>, now: DateTime<Utc>) {
+        let run_period = self.run_period(model, initdate);
+        let next = next_run_available(initdate, run_period, self.publish_delay);
+        let next = if next <= now { now + self.retry } else { next };
+
+        tracing::debug!(
+            model = model,
+            run_period_hours = run_period.num_hours(),
+            next_fetch = %next,
+            "scheduled next forecast fetch"
+        );
+        self.next_fetch.insert(model, next);
+    }
+
+    /// Period of a model learned from the init times of its runs, until two runs were seen
+    /// the configured one is used as long as it fits the init time
+    fn run_period(&mut self, model: IdModel, initdate: DateTime<Utc>) -> Duration {
+        let runs = self.runs.entry(model).or_insert(ModelRuns {
+            last_init: initdate,
+            period: None,
+        });
+
+        if initdate > runs.last_init {
+            let interval = initdate - runs.last_init;
+            runs.period = Some(runs.period.map_or(interval, |period| period.min(interval)));
+            runs.last_init = initdate;
+        }
+
+        runs.period
+            .unwrap_or_else(|| aligned_run_period(initdate, self.default_run_period))
+    }
+
+    /// Schedules the models which were due in a cycle. Runs skipped as already ingested are
+    /// not fetched, the latest ingested run of such a model stands in for the fetched one.
+    pub fn record_cycle(
+        &mut self,
+        due_models: &[IdModel],
+        fetched_runs: BTreeMap<IdModel, DateTime<Utc>>,
+        ingested_runs: &BTreeMap<IdSpot, BTreeMap<IdModel, IngestedRun>>,
+        now: DateTime<Utc>,
+    ) {
+        let mut runs = fetched_runs;
+        for model in due_models {
+            let ingested = ingested_runs
+                .values()
+                .filter_map(|runs| runs.get(model))
+                .map(|run| run.init_time)
+                .max();
+            if let (None, Some(init_time)) = (runs.get(model), ingested) {
+                runs.insert(*model, init_time);
+            }
+        }
+
+        runs.into_iter()
+            .for_each(|(model, initdate)| self.record_run(model, initdate, now));
+        self.record_misses(now);
+    }
+
+    /// Postpones every model which was due but did not deliver a run in the last cycle
+    pub fn record_misses(&mut self, now: DateTime<Utc>) {
+        let retry_at = now + self.retry;
+
+        self.next_fetch
+            .values_mut()
+            .filter(|next| **next <= now)
+            .for_each(|next| *next = retry_at);
+
+        if self.next_fetch.is_empty() {
+            self.fallback = retry_at;
+        }
+    }
+}
+
+/// Fixed interval schedule of station fetches, remembering up to when readings were requested
+pub struct StationSchedule {
+    interval: Duration,
+    next_fetch: DateTime<Utc>,
+    fetched_until: DateTime<Utc>,
+}
+
+impl StationSchedule {
+    pub fn new(config: &SchedulerConfig, fetched_until: DateTime<Utc>, now: DateTime<Utc>) -> Self {
+        Self {
+            interval: Duration::minutes(config.station_interval_minutes),
+            next_fetch: now,
+            fetched_until,
+        }
+    }
+
+    pub fn next_due(&self) -> DateTime<Utc> {
+        self.next_fetch
+    }
+
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.next_fetch <= now
+    }
+
+    /// Returns the range of readings to request and schedules the next fetch
+    pub fn advance(&mut self, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        let range = (self.fetched_until, now);
+
+        self.fetched_until = now;
+        self.next_fetch = now + self.interval;
+
+        range
+    }
+}
+
+/// Models publish a new run every `run_period` after their init time, the data
+/// becomes available roughly `publish_delay` later
+fn next_run_available(
+    initdate: DateTime<Utc>,
+    run_period: Duration,
+    publish_delay: Duration,
+) -> DateTime<Utc> {
+    initdate + run_period + publish_delay
+}
+
+/// Models issue their runs at the same hours every day, so the period has to divide the
+/// hours from midnight to the init time, e.g. a run at 03:00 rules out a 6 hour period
+fn aligned_run_period(initdate: DateTime<Utc>, run_period: Duration) -> Duration {
+    let period_hours = run_period.num_hours();
+    let init_hour = i64::from(initdate.hour());
+
+    if period_hours <= 0 || init_hour % period_hours == 0 {
+        return run_period;
+    }
+
+    let (mut a, mut b) = (init_hour, period_hours);
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    Duration::hours(a)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn test_config() -> SchedulerConfig {
+        SchedulerConfig {
+            station_interval_minutes: 30,
+            forecast_run_period_hours: 6,
+            forecast_publish_delay_minutes: 240,
+            retry_minutes: 30,
+        }
+    }
+
+    #[test]
+    fn schedules_next_run_after_init_time() {
+        let now = Utc.with_ymd_and_hms(2023, 4, 8, 16, 45, 0).unwrap();
+        let initdate = Utc.with_ymd_and_hms(2023, 4, 8, 12, 0, 0).unwrap();
+        let mut schedule = ForecastSchedule::new(&test_config(), &[3], now);
+        assert!(schedule.is_due(now));
+
+        schedule.record_run(3, initdate, now);
+
+        assert_eq!(
+            schedule.next_due(),
+            Utc.with_ymd_and_hms(2023, 4, 8, 22, 0, 0).unwrap()
+        );
+        assert!(!schedule.is_due(now));
+    }
+
+    #[test]
+    fn retries_when_fetched_run_is_stale() {
+        let now = Utc.with_ymd_and_hms(2023, 4, 9, 8, 0, 0).unwrap();
+        let initdate = Utc.with_ymd_and_hms(2023, 4, 8, 12, 0, 0).unwrap();
+        let mut schedule = ForecastSchedule::new(&test_config(), &[3, 42], now);
+
+        schedule.record_run(3, initdate, now);
+        schedule.record_misses(now);
+
+        assert_eq!(schedule.next_due(), now + Duration::minutes(30));
+    }
+
+    #[test]
+    fn learns_run_period_of_each_model() {
+        let now = Utc.with_ymd_and_hms(2023, 4, 8, 1, 0, 0).unwrap();
+        let midnight = Utc.with_ymd_and_hms(2023, 4, 8, 0, 0, 0).unwrap();
+        let mut schedule = ForecastSchedule::new(&test_config(), &[3, 45], now);
+
+        // runs at 03:00 do not fit the configured 6 hour period
+        schedule.record_run(45, midnight + Duration::hours(3), now);
+        assert_eq!(
+            schedule.next_fetch[&45],
+            midnight + Duration::hours(3 + 3 + 4)
+        );
+
+        // two runs of a 24 hour model replace the configured period
+        schedule.record_run(3, midnight - Duration::days(1), now);
+        schedule.record_run(3, midnight, now);
+        assert_eq!(schedule.next_fetch[&3], midnight + Duration::hours(24 + 4));
+    }
+
+    #[test]
+    fn schedules_run_skipped_as_ingested() {
+        let now = Utc.with_ymd_and_hms(2023, 4, 8, 16, 45, 0).unwrap();
+        let midnight = Utc.with_ymd_and_hms(2023, 4, 8, 0, 0, 0).unwrap();
+        let ingested_run = |init_time| IngestedRun {
+            init_time,
+            rundef: String::new(),
+            update_last: now.naive_utc(),
+        };
+        let mut schedule = ForecastSchedule::new(&test_config(), &[3, 45], now);
+
+        // after a restart the latest run of model 3 is already stored for both spots
+        let ingested_runs = BTreeMap::from([
+            (
+                36048,
+                BTreeMap::from([(3, ingested_run(midnight + Duration::hours(6)))]),
+            ),
+            (
+                48009,
+                BTreeMap::from([(3, ingested_run(midnight + Duration::hours(12)))]),
+            ),
+        ]);
+        schedule.record_cycle(&[3, 45], BTreeMap::new(), &ingested_runs, now);
+
+        assert_eq!(
+            schedule.next_fetch[&3],
+            midnight + Duration::hours(12 + 6 + 4)
+        );
+        assert_eq!(schedule.next_fetch[&45], now + Duration::minutes(30));
+
+        // the skipped run seeds the period learned from the next one
+        let later = midnight + Duration::hours(22);
+        schedule.record_cycle(
+            &[3],
+            BTreeMap::from([(3, midnight + Duration::days(1))]),
+            &ingested_runs,
+            later,
+        );
+        assert_eq!(
+            schedule.next_fetch[&3],
+            midnight + Duration::days(1) + Duration::hours(12 + 4)
+        );
+    }
+
+    #[test]
+    fn station_ranges_are_contiguous() {
+        let start = Utc.with_ymd_and_hms(2023, 4, 8, 0, 0, 0).unwrap();
+        let now = Utc.with_ymd_and_hms(2023, 4, 8, 10, 0, 0).unwrap();
+        let mut schedule = StationSchedule::new(&test_config(), start, now);
+
+        assert_eq!(schedule.advance(now), (start, now));
+
+        let later = now + Duration::minutes(30);
+        assert!(schedule.is_due(later));
+        assert_eq!(schedule.advance(later), (now, later));
+    }
+}
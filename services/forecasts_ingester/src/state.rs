@@ -7,108 +7,614 @@ use crate::{
             ingesting::IngestMsg,
         },
     },
-    config::{DataStorage, Settings},
-    data_fetcher::{client::FetchingClient, errors::FetchError, DataFetcher},
-    data_ingester::{errors::IngestError, DataIngester},
+    analysis::{
+        verification::{
+            compute_verification, load_forecast_observations, store_verification,
+            VerificationParams,
+        },
+        volatility::{compute_volatility, load_forecast_points, VolatilityParams},
+    },
+    backfill::{is_chunk_present, split_into_chunks, BackfillParams},
+    config::{DataStorage, SchedulerConfig, Settings},
+    data_fetcher::{client::FetchingClient, errors::FetchError, DataFetcher, FetchResults},
+    data_ingester::{
+        errors::IngestError, parquet_repository::ParquetRepository,
+        postgres_repository::PostgresRepository, s3_repository::S3Repository, DataIngester,
+        IngestCounts, IngestedRun,
+    },
+    export::{load_forecast_records, load_station_reading_records, ExportParams},
+    scheduler::{ForecastSchedule, StationSchedule},
     types::windguru::{
         forecast::{IdModel, IdSpot},
-        station::WindguruStationFetchParams,
+        station::{IdStation, SpotStation, WindguruStationFetchParams},
     },
+    watch_list::{LoadedWatchList, WatchList, WatchListLoader, WatchedSpot},
 };
 use actix::*;
+use anyhow::{bail, Context};
 use chrono::{DateTime, Duration, Utc};
-use sqlx::{Pool, Postgres};
-use std::sync::Arc;
+use sqlx::{PgPool, Pool, Postgres};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
 
-use tokio::task::JoinSet;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    task::JoinSet,
+};
 
 pub struct State {
     // data_fetcher: Box<dyn DataFetcher<WindguruForecasts>>,
     // data_ingester: Box<dyn DataIngester>,
 }
 
-fn get_yesterday_date_bounds() -> (DateTime<Utc>, DateTime<Utc>) {
+fn get_today_date_bounds() -> (DateTime<Utc>, DateTime<Utc>) {
     let now = Utc::now();
-    let yesterday_start = DateTime::from_utc(now.date_naive().and_hms_opt(0, 0, 0).unwrap(), Utc);
-    let yesterday_end = yesterday_start + Duration::minutes(23 * 60 + 59);
+    let today_start = now.date_naive().and_hms_opt(0, 0, 0).unwrap().and_utc();
+    let today_end = today_start + Duration::minutes(23 * 60 + 59);
 
-    (yesterday_start, yesterday_end)
+    (today_start, today_end)
 }
 
+/// Watch list managed by watcher-settings, the windguru section of the settings when it is
+/// not configured or unreachable
+fn watch_list_loader(settings: &Settings) -> WatchListLoader {
+    WatchListLoader::new(
+        settings.watch_list.as_ref(),
+        WatchList::from_config(&settings.windguru),
+    )
+}
+
+async fn connect_postgres(connection_url: &str) -> PgPool {
+    Pool::<Postgres>::connect(connection_url).await.unwrap()
+}
+
+/// Analyses and exports query stored data, which is possible only with postgresql storage
+async fn connect_queryable_storage(storage: &DataStorage) -> anyhow::Result<PgPool> {
+    match storage {
+        DataStorage::Postgresql(config) => Ok(connect_postgres(&config.connection_url).await),
+        DataStorage::S3(_) | DataStorage::Parquet(_) => {
+            bail!("querying stored data requires postgresql storage")
+        }
+    }
+}
+
+/// Latest completely stored run of each model per spot, spots which could not be checked are
+/// fetched in full
+async fn load_ingested_runs<DI: DataIngester>(
+    spots: &[WatchedSpot],
+    data_ingester: &DI,
+) -> BTreeMap<IdSpot, BTreeMap<IdModel, IngestedRun>> {
+    let mut ingested_runs = BTreeMap::new();
+
+    for spot in spots {
+        match data_ingester.ingested_runs(spot.id_spot).await {
+            Ok(runs) => {
+                ingested_runs.insert(spot.id_spot, runs);
+            }
+            Err(err) => tracing::warn!(
+                spot = spot.id_spot,
+                "unable to check ingested runs, fetching anyway {}",
+                err
+            ),
+        }
+    }
+
+    ingested_runs
+}
+
+/// Spots without selected models are fetched with every offered model,
+/// others only with those of their models which are due
+fn forecast_fetch_msgs(
+    spots: &[WatchedSpot],
+    due_models: &[IdModel],
+    ingested_runs: &BTreeMap<IdSpot, BTreeMap<IdModel, IngestedRun>>,
+) -> Vec<FetchMsg> {
+    spots
+        .iter()
+        .filter_map(|spot| {
+            let models: Vec<IdModel> = spot
+                .models
+                .iter()
+                .filter(|model| due_models.contains(model))
+                .copied()
+                .collect();
+            if models.is_empty() && !spot.models.is_empty() {
+                return None;
+            }
+
+            Some(FetchMsg::WindguruForecast(WindguruForecastFetchMsg {
+                spot: spot.id_spot,
+                models,
+                ingested_runs: ingested_runs
+                    .get(&spot.id_spot)
+                    .cloned()
+                    .unwrap_or_default(),
+            }))
+        })
+        .collect()
+}
+
+/// Station shared by several spots is fetched only once
+fn station_fetch_msgs(
+    stations: &[SpotStation],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Vec<FetchMsg> {
+    stations
+        .iter()
+        .map(|station| (station.id_station, station.avg_minutes))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|(id_station, avg_minutes)| {
+            FetchMsg::WindguruStation(WindguruStationFetchParams {
+                id_station,
+                from,
+                to,
+                avg_minutes,
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+async fn persist_spot_stations<DI>(
+    stations: &[SpotStation],
+    ingester_addr: &Addr<IngestingActor<DI>>,
+) where
+    DI: DataIngester + Clone + 'static,
+{
+    let msg = IngestMsg::WindguruSpotStations(stations.to_vec());
+
+    match ingester_addr.send(msg).await {
+        Ok(Ok(_)) => tracing::debug!("persisted spot to station mapping"),
+        Ok(Err(err)) => tracing::error!("unable to persist spot to station mapping {}", err),
+        Err(err) => tracing::error!("ingesting actor unavailable {}", err),
+    }
+}
+
+/// Fetches and ingests given messages, returns init times of the model runs which were fetched
 async fn issue_fetching_msgs<DF, DI>(
-    spots: &[IdSpot],
-    _models: &[IdModel],
+    msgs: Vec<FetchMsg>,
     fetcher_addr: &Addr<FetchingActor<DF>>,
     ingester_addr: &Addr<IngestingActor<DI>>,
-) where
+) -> BTreeMap<IdModel, DateTime<Utc>>
+where
     DF: DataFetcher + Clone + 'static,
     DI: DataIngester + Clone + 'static,
 {
-    let mut fetch_tasks: JoinSet<Result<Result<IngestMsg, FetchError>, MailboxError>> =
+    let mut fetch_tasks: JoinSet<Result<FetchResults, MailboxError>> = JoinSet::new();
+    let mut ingest_tasks: JoinSet<Result<Result<IngestCounts, IngestError>, MailboxError>> =
         JoinSet::new();
-    let mut ingest_tasks: JoinSet<Result<Result<(), IngestError>, MailboxError>> = JoinSet::new();
-    let (start, end) = get_yesterday_date_bounds();
-
-    spots.iter().for_each(|spot| {
-        let forecast_msg = FetchMsg::WindguruForecast(WindguruForecastFetchMsg { spot: *spot });
-        let station_msg = FetchMsg::WindguruStation(WindguruStationFetchParams {
-            id_station: 2764,
-            from: start,
-            to: end,
-            avg_minutes: 5,
-            ..Default::default()
-        });
-
-        tracing::debug!(spot = spot, "issueing forecast fetch message {}", forecast_msg);
-        fetch_tasks.spawn(fetcher_addr.send(forecast_msg));
-
-        tracing::debug!(
-            station = 2764,
-            "issueing station fetch message {}",
-            station_msg
-        );
-        fetch_tasks.spawn(fetcher_addr.send(station_msg));
+    let mut model_runs = BTreeMap::new();
+
+    msgs.into_iter().for_each(|msg| {
+        tracing::debug!("issueing fetch message {}", msg);
+        fetch_tasks.spawn(fetcher_addr.send(msg));
     });
 
     while let Some(res) = fetch_tasks.join_next().await {
-        let res = res.unwrap().unwrap();
-        match res {
-            Ok(msg) => {
-                tracing::debug!("issueing ingest message {}", msg);
-                ingest_tasks.spawn(ingester_addr.send(msg));
-            },
+        let results = match res {
+            Ok(Ok(results)) => results,
+            Ok(Err(err)) => {
+                tracing::error!("fetching actor unavailable {}", err);
+                continue;
+            }
             Err(err) => {
-                tracing::error!("error after fetching message {}", err);
+                tracing::error!("fetching task failed {}", err);
+                continue;
+            }
+        };
+
+        for result in results {
+            match result {
+                Ok(msg) => {
+                    let wgmodel = match &msg {
+                        IngestMsg::WindguruForecast(forecast) => Some(&forecast.forecast.wgmodel),
+                        IngestMsg::WindguruWaveForecast(forecast) => {
+                            Some(&forecast.forecast.wgmodel)
+                        }
+                        _ => None,
+                    };
+                    if let Some(wgmodel) = wgmodel {
+                        model_runs.insert(wgmodel.id_model, wgmodel.initdate);
+                    }
+
+                    tracing::debug!("issueing ingest message {}", msg);
+                    ingest_tasks.spawn(ingester_addr.send(msg));
+                }
+                Err(err @ FetchError::MissingModel { .. }) => {
+                    tracing::warn!("skipping model {}", err)
+                }
+                Err(err) => tracing::error!("error after fetching message {}", err),
             }
         }
     }
 
     while let Some(res) = ingest_tasks.join_next().await {
-        let _ = res.unwrap().unwrap().unwrap();
-        tracing::debug!("successully ingested data");
+        match res {
+            Ok(Ok(Ok(counts))) => tracing::debug!(
+                inserted = counts.inserted,
+                updated = counts.updated,
+                skipped = counts.skipped,
+                "successully ingested data"
+            ),
+            Ok(Ok(Err(err))) => tracing::error!("error while ingesting data {}", err),
+            Ok(Err(err)) => tracing::error!("ingesting actor unavailable {}", err),
+            Err(err) => tracing::error!("ingesting task failed {}", err),
+        }
+    }
+
+    model_runs
+}
+
+/// Runs fetching cycles whenever forecasts or stations are due until SIGTERM or SIGINT arrives,
+/// the watch list is reloaded at the start of every cycle
+async fn run_scheduler<DF, DI>(
+    config: SchedulerConfig,
+    watch_list_loader: &WatchListLoader,
+    data_ingester: &DI,
+    fetcher_addr: &Addr<FetchingActor<DF>>,
+    ingester_addr: &Addr<IngestingActor<DI>>,
+) where
+    DF: DataFetcher + Clone + 'static,
+    DI: DataIngester + Clone + 'static,
+{
+    let mut sigterm = signal(SignalKind::terminate()).expect("unable to listen for SIGTERM");
+
+    let now = Utc::now();
+    let (today_start, _) = get_today_date_bounds();
+    let loaded = watch_list_loader.load().await;
+    let mut persisted = !loaded.is_fallback();
+    let mut watch_list = loaded.into_inner();
+    let mut forecasts = ForecastSchedule::new(&config, &watch_list.models(), now);
+    let mut stations = StationSchedule::new(&config, today_start, now);
+    if persisted {
+        persist_spot_stations(&watch_list.spot_stations(), ingester_addr).await;
+    }
+
+    loop {
+        let next_due = forecasts.next_due().min(stations.next_due());
+        let sleep_for = (next_due - Utc::now()).to_std().unwrap_or_default();
+        tracing::info!(next_cycle = %next_due, "waiting for next fetching cycle");
+
+        tokio::select! {
+            _ = tokio::time::sleep(sleep_for) => {},
+            _ = sigterm.recv() => {
+                tracing::info!("received SIGTERM, stopping scheduler");
+                break;
+            },
+            _ = tokio::signal::ctrl_c() => {
+                tracing::info!("received SIGINT, stopping scheduler");
+                break;
+            },
+        }
+
+        // the last watch list is kept while its source is unreachable
+        if let LoadedWatchList::Source(reloaded) = watch_list_loader.load().await {
+            if reloaded != watch_list || !persisted {
+                tracing::info!(spots = reloaded.spots.len(), "watch list changed");
+                persist_spot_stations(&reloaded.spot_stations(), ingester_addr).await;
+                watch_list = reloaded;
+                persisted = true;
+            }
+        }
+
+        let now = Utc::now();
+        forecasts.watch(&watch_list.models(), now);
+        let fetch_forecasts = forecasts.is_due(now);
+        let due_models = forecasts.due_models(now);
+        let mut ingested_runs = BTreeMap::new();
+        let mut msgs = Vec::new();
+
+        if fetch_forecasts {
+            ingested_runs = load_ingested_runs(&watch_list.spots, data_ingester).await;
+            msgs.extend(forecast_fetch_msgs(
+                &watch_list.spots,
+                &due_models,
+                &ingested_runs,
+            ));
+        }
+        if stations.is_due(now) {
+            let (from, to) = stations.advance(now);
+            msgs.extend(station_fetch_msgs(&watch_list.spot_stations(), from, to));
+        }
+
+        let model_runs = issue_fetching_msgs(msgs, fetcher_addr, ingester_addr).await;
+
+        if fetch_forecasts {
+            forecasts.record_cycle(&due_models, model_runs, &ingested_runs, Utc::now());
+        }
     }
 }
 
+/// Fetches and ingests readings of a single station chunk, returns counts of ingested readings
+async fn backfill_chunk<DF, DI>(
+    params: WindguruStationFetchParams,
+    fetcher_addr: &Addr<FetchingActor<DF>>,
+    ingester_addr: &Addr<IngestingActor<DI>>,
+) -> anyhow::Result<IngestCounts>
+where
+    DF: DataFetcher + Clone + 'static,
+    DI: DataIngester + Clone + 'static,
+{
+    let mut ingested = IngestCounts::default();
+
+    for result in fetcher_addr.send(FetchMsg::WindguruStation(params)).await? {
+        ingested += ingester_addr.send(result?).await??;
+    }
+
+    Ok(ingested)
+}
+
+/// Backfills every chunk of the stations, returns the number of chunks which failed
+async fn run_backfill<DF, DI>(
+    params: &BackfillParams,
+    stations: Vec<(IdStation, u32)>,
+    data_ingester: &DI,
+    fetcher_addr: &Addr<FetchingActor<DF>>,
+    ingester_addr: &Addr<IngestingActor<DI>>,
+) -> usize
+where
+    DF: DataFetcher + Clone + 'static,
+    DI: DataIngester + Clone + 'static,
+{
+    let (from, to) = params.range();
+    let chunks = split_into_chunks(from, to, Duration::hours(params.chunk_hours));
+    let mut failed = 0;
+
+    for (id_station, avg_minutes) in stations {
+        for (i, (chunk_from, chunk_to)) in chunks.iter().copied().enumerate() {
+            let progress = format!("{}/{}", i + 1, chunks.len());
+
+            match data_ingester
+                .station_readings_count(id_station, avg_minutes, chunk_from, chunk_to)
+                .await
+            {
+                Ok(stored) if is_chunk_present(stored, chunk_from, chunk_to, avg_minutes) => {
+                    tracing::info!(
+                        station = id_station,
+                        chunk = progress,
+                        from = %chunk_from,
+                        to = %chunk_to,
+                        "readings already present, skipping chunk"
+                    );
+                    continue;
+                }
+                Ok(_) => {}
+                Err(err) => tracing::warn!(
+                    station = id_station,
+                    chunk = progress,
+                    "unable to check stored readings, fetching anyway {}",
+                    err
+                ),
+            }
+
+            let fetch_params = WindguruStationFetchParams {
+                id_station,
+                from: chunk_from,
+                to: chunk_to,
+                avg_minutes,
+                ..Default::default()
+            };
+
+            match backfill_chunk(fetch_params, fetcher_addr, ingester_addr).await {
+                Ok(readings) => tracing::info!(
+                    station = id_station,
+                    chunk = progress,
+                    from = %chunk_from,
+                    to = %chunk_to,
+                    inserted = readings.inserted,
+                    updated = readings.updated,
+                    skipped = readings.skipped,
+                    "backfilled chunk"
+                ),
+                Err(err) => {
+                    failed += 1;
+                    tracing::error!(
+                        station = id_station,
+                        chunk = progress,
+                        from = %chunk_from,
+                        to = %chunk_to,
+                        "backfilling chunk failed {}",
+                        err
+                    )
+                }
+            }
+        }
+    }
+
+    failed
+}
+
 impl State {
     pub async fn start(settings: Settings) {
-        let data_fetcher = Arc::new(FetchingClient::from(&settings));
+        match &settings.storage {
+            DataStorage::Postgresql(config) => {
+                let pool = connect_postgres(&config.connection_url).await;
+                let data_ingester = PostgresRepository::new(pool, config);
+                Self::run(&settings, data_ingester).await
+            }
+            DataStorage::S3(config) => {
+                let data_ingester = S3Repository::new(config).await;
+                Self::run(&settings, data_ingester).await
+            }
+            DataStorage::Parquet(config) => {
+                let data_ingester = ParquetRepository::new(config);
+                Self::run(&settings, data_ingester).await
+            }
+        }
+    }
+
+    async fn run<DI>(settings: &Settings, data_ingester: DI)
+    where
+        DI: DataIngester + Clone + 'static,
+    {
+        let data_fetcher = Arc::new(FetchingClient::from(settings));
 
-        let data_ingester = match settings.storage {
-            DataStorage::Postgresql(config) => Pool::<Postgres>::connect(&config.connection_url)
+        let fetcher_addr = FetchingActor::new(data_fetcher).start();
+        let ingester_addr = IngestingActor::new(data_ingester.clone()).start();
+
+        let watch_list_loader = watch_list_loader(settings);
+
+        match &settings.scheduler {
+            Some(scheduler_config) => {
+                run_scheduler(
+                    scheduler_config.clone(),
+                    &watch_list_loader,
+                    &data_ingester,
+                    &fetcher_addr,
+                    &ingester_addr,
+                )
                 .await
-                .unwrap(),
-            _ => unimplemented!(),
-        };
+            }
+            None => {
+                let loaded = watch_list_loader.load().await;
+                let persist = !loaded.is_fallback();
+                let watch_list = loaded.into_inner();
+                let spot_stations = watch_list.spot_stations();
+                if persist {
+                    persist_spot_stations(&spot_stations, &ingester_addr).await;
+                }
+
+                let (start, end) = get_today_date_bounds();
+                let ingested_runs = load_ingested_runs(&watch_list.spots, &data_ingester).await;
+                let mut msgs =
+                    forecast_fetch_msgs(&watch_list.spots, &watch_list.models(), &ingested_runs);
+                msgs.extend(station_fetch_msgs(&spot_stations, start, end));
+
+                issue_fetching_msgs(msgs, &fetcher_addr, &ingester_addr).await;
+            }
+        }
+    }
+
+    /// Returns the number of chunks which could not be backfilled
+    pub async fn backfill(settings: Settings, params: BackfillParams) -> usize {
+        match &settings.storage {
+            DataStorage::Postgresql(config) => {
+                let pool = connect_postgres(&config.connection_url).await;
+                let data_ingester = PostgresRepository::new(pool, config);
+                Self::backfill_with(&settings, &params, data_ingester).await
+            }
+            DataStorage::S3(config) => {
+                let data_ingester = S3Repository::new(config).await;
+                Self::backfill_with(&settings, &params, data_ingester).await
+            }
+            DataStorage::Parquet(config) => {
+                let data_ingester = ParquetRepository::new(config);
+                Self::backfill_with(&settings, &params, data_ingester).await
+            }
+        }
+    }
+
+    async fn backfill_with<DI>(
+        settings: &Settings,
+        params: &BackfillParams,
+        data_ingester: DI,
+    ) -> usize
+    where
+        DI: DataIngester + Clone + 'static,
+    {
+        let loaded = watch_list_loader(settings).load().await;
+        let persist = !loaded.is_fallback();
+        let spot_stations = loaded.into_inner().spot_stations();
+        let stations = params.select_stations(&spot_stations);
+        if stations.is_empty() {
+            tracing::warn!("no stations selected for backfill");
+            return 0;
+        }
+
+        let data_fetcher = Arc::new(FetchingClient::from(settings));
 
         let fetcher_addr = FetchingActor::new(data_fetcher).start();
-        let ingester_addr = IngestingActor::new(data_ingester).start();
+        let ingester_addr = IngestingActor::new(data_ingester.clone()).start();
+        if persist {
+            persist_spot_stations(&spot_stations, &ingester_addr).await;
+        }
 
-        issue_fetching_msgs(
-            &settings.windguru.spots,
-            &settings.windguru.models,
+        run_backfill(
+            params,
+            stations.into_iter().collect(),
+            &data_ingester,
             &fetcher_addr,
             &ingester_addr,
         )
-        .await;
+        .await
+    }
+
+    pub async fn analyse_volatility(
+        settings: Settings,
+        params: VolatilityParams,
+    ) -> anyhow::Result<()> {
+        let pool = connect_queryable_storage(&settings.storage).await?;
+
+        let points = load_forecast_points(&pool, &params)
+            .await
+            .context("unable to load forecasts")?;
+        tracing::info!(forecasts = points.len(), "loaded forecasts for analysis");
+
+        for report in compute_volatility(points, &params.thresholds) {
+            println!("{report}");
+        }
+
+        Ok(())
+    }
+
+    pub async fn verify_forecasts(
+        settings: Settings,
+        params: VerificationParams,
+    ) -> anyhow::Result<()> {
+        let pool = connect_queryable_storage(&settings.storage).await?;
+
+        let observations = load_forecast_observations(&pool, &params)
+            .await
+            .context("unable to load forecasts with observations")?;
+        tracing::info!(
+            forecasts = observations.len(),
+            "loaded forecasts paired with station readings"
+        );
+
+        let verification = compute_verification(&observations);
+        let rows_affected = store_verification(&pool, &params, &verification)
+            .await
+            .context("unable to store verification statistics")?;
+        tracing::info!(rows_affected, "stored verification statistics");
+
+        for lead_time in verification {
+            println!("{lead_time}");
+        }
+
+        Ok(())
+    }
+
+    pub async fn export(settings: Settings, params: ExportParams) -> anyhow::Result<()> {
+        let pool = connect_queryable_storage(&settings.storage).await?;
+        let repository = ParquetRepository::from_directory(params.output.clone());
+
+        let records = load_forecast_records(&pool, &params)
+            .await
+            .context("unable to load forecasts")?;
+        tracing::info!(forecasts = records.len(), "loaded forecasts for export");
+        let files = repository
+            .write_forecasts(records)
+            .await
+            .context("unable to export forecasts")?;
+        tracing::info!(files, "exported forecasts");
+
+        let records = load_station_reading_records(&pool, &params)
+            .await
+            .context("unable to load station readings")?;
+        tracing::info!(
+            readings = records.len(),
+            "loaded station readings for export"
+        );
+        let files = repository
+            .write_station_readings(records)
+            .await
+            .context("unable to export station readings")?;
+        tracing::info!(files, "exported station readings");
+
+        Ok(())
     }
 }
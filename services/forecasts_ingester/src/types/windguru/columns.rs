@@ -0,0 +1,177 @@
+use std::marker::PhantomData;
+
+use serde::de::{Error, Unexpected};
+use serde_json::{Map, Value};
+
+/// Variables of a windguru response given as parallel arrays, one value per data point
+pub struct Columns<E> {
+    data: Map<String, Value>,
+    len: usize,
+    error: PhantomData<E>,
+}
+
+impl<E: Error> Columns<E> {
+    /// Every column read later has to be as long as the `index` one
+    pub fn new(data: Map<String, Value>, index: &str) -> Result<Self, E> {
+        let len = array(&data, index)?
+            .ok_or_else(|| missing_field(index))?
+            .len();
+
+        Ok(Self {
+            data,
+            len,
+            error: PhantomData,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn value(&self, key: &str) -> Result<&Value, E> {
+        self.data.get(key).ok_or_else(|| missing_field(key))
+    }
+
+    pub fn i64(&self, key: &str) -> Result<i64, E> {
+        let value = self.value(key)?;
+        value
+            .as_i64()
+            .ok_or_else(|| invalid_type(key, value, "an integer"))
+    }
+
+    /// Column which has to be present, with no missing values
+    pub fn required<'a, T>(
+        &'a self,
+        key: &str,
+        parse: impl Fn(&'a Value) -> Option<T>,
+        expected: &str,
+    ) -> Result<Vec<T>, E> {
+        let values = self.column(key)?.ok_or_else(|| missing_field(key))?;
+
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                parse(value).ok_or_else(|| invalid_type(&format!("{key}[{i}]"), value, expected))
+            })
+            .collect()
+    }
+
+    /// Column of optional floats, all of them missing when the column is absent
+    pub fn f32s(&self, key: &str) -> Result<Vec<Option<f32>>, E> {
+        self.optional(
+            key,
+            |value| value.as_f64().map(|val| val as f32),
+            "a number",
+        )
+    }
+
+    /// Column of optional integers, all of them missing when the column is absent
+    pub fn i32s(&self, key: &str) -> Result<Vec<Option<i32>>, E> {
+        self.optional(
+            key,
+            |value| value.as_f64().map(|val| val.round() as i32),
+            "a number",
+        )
+    }
+
+    /// Column of optional values, all of them missing when the column is absent, a present
+    /// column has to be as long as the index one so that values stay aligned with it
+    fn optional<T>(
+        &self,
+        key: &str,
+        parse: impl Fn(&Value) -> Option<T>,
+        expected: &str,
+    ) -> Result<Vec<Option<T>>, E> {
+        let Some(values) = self.column(key)? else {
+            return Ok((0..self.len).map(|_| None).collect());
+        };
+
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| match value {
+                Value::Null => Ok(None),
+                value => parse(value)
+                    .map(Some)
+                    .ok_or_else(|| invalid_type(&format!("{key}[{i}]"), value, expected)),
+            })
+            .collect()
+    }
+
+    /// Column which has to be as long as the index one
+    fn column(&self, key: &str) -> Result<Option<&Vec<Value>>, E> {
+        let values = array(&self.data, key)?;
+
+        match values {
+            Some(values) if values.len() != self.len => Err(E::custom(format!(
+                "{key} has {} values, expected {}",
+                values.len(),
+                self.len
+            ))),
+            values => Ok(values),
+        }
+    }
+}
+
+fn array<'a, E: Error>(
+    data: &'a Map<String, Value>,
+    key: &str,
+) -> Result<Option<&'a Vec<Value>>, E> {
+    match data.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Array(values)) => Ok(Some(values)),
+        Some(value) => Err(invalid_type(key, value, "an array")),
+    }
+}
+
+fn missing_field<E: Error>(key: &str) -> E {
+    E::custom(format!("missing field `{key}`"))
+}
+
+fn invalid_type<E: Error>(key: &str, value: &Value, expected: &str) -> E {
+    let unexpected = match value {
+        Value::Null => Unexpected::Unit,
+        Value::Bool(value) => Unexpected::Bool(*value),
+        Value::Number(_) => Unexpected::Other("number"),
+        Value::String(value) => Unexpected::Str(value),
+        Value::Array(_) => Unexpected::Seq,
+        Value::Object(_) => Unexpected::Map,
+    };
+
+    E::custom(format!("{key}: {}", E::invalid_type(unexpected, &expected)))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::*;
+
+    fn columns(data: Value) -> Columns<serde_json::Error> {
+        let Value::Object(data) = data else {
+            unreachable!("test data is an object")
+        };
+        Columns::new(data, "hours").unwrap()
+    }
+
+    #[test]
+    fn rejects_uneven_optional_columns() {
+        let columns = columns(json!({
+            "hours": [0, 1, 2],
+            "SLP": [1012, null],
+            "GUST": [9.4, 10, 11, 12],
+        }));
+
+        assert_eq!(
+            columns.i32s("SLP").unwrap_err().to_string(),
+            "SLP has 2 values, expected 3"
+        );
+        assert_eq!(
+            columns.f32s("GUST").unwrap_err().to_string(),
+            "GUST has 4 values, expected 3"
+        );
+        assert_eq!(columns.f32s("TMPE").unwrap(), vec![None, None, None]);
+    }
+}
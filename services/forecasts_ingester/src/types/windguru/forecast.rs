@@ -1,9 +1,11 @@
 use std::collections::HashMap;
 
+use super::station::SpotStation;
 use super::windguru_datetime_format;
+use crate::config::RateLimitConfig;
 use anyhow::anyhow;
 use chrono::{DateTime, Duration, NaiveDateTime, NaiveTime, Utc};
-use serde::{de, Deserialize};
+use serde::{de, Deserialize, Serialize};
 
 pub type IdSpot = i32;
 pub type IdModel = i32;
@@ -13,6 +15,11 @@ pub struct WindguruConfig {
     pub url: String,
     pub spots: Vec<IdSpot>,
     pub models: Vec<IdModel>,
+    #[serde(default)]
+    pub stations: Vec<SpotStation>,
+    /// Limits of requests sent to Windguru
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
 }
 
 #[derive(Deserialize, Debug)]
@@ -25,18 +32,49 @@ pub struct ForecastParamsMetadata {
 }
 
 #[derive(Deserialize, Debug)]
+#[serde(from = "WindguruForecastsResponse")]
 pub struct WindguruForecasts {
     pub id_spot: IdSpot,
     pub wgmodel: WgModel,
-    #[serde(with = "windguru_hour_minutes_format")]
     pub sunrise: NaiveTime,
-    #[serde(with = "windguru_hour_minutes_format")]
     pub sunset: NaiveTime,
-    #[serde(with = "forecasts_arrays_format")]
-    #[serde(rename = "fcst")]
+    /// Time windguru last updated the run
+    pub update_last: NaiveDateTime,
     pub forecasts: Vec<Fcst>,
 }
 
+#[derive(Deserialize)]
+struct WindguruForecastsResponse {
+    id_spot: IdSpot,
+    wgmodel: WgModel,
+    #[serde(with = "windguru_hour_minutes_format")]
+    sunrise: NaiveTime,
+    #[serde(with = "windguru_hour_minutes_format")]
+    sunset: NaiveTime,
+    #[serde(with = "forecasts_arrays_format")]
+    fcst: RunForecasts<Fcst>,
+}
+
+impl From<WindguruForecastsResponse> for WindguruForecasts {
+    fn from(response: WindguruForecastsResponse) -> Self {
+        Self {
+            id_spot: response.id_spot,
+            wgmodel: response.wgmodel,
+            sunrise: response.sunrise,
+            sunset: response.sunset,
+            update_last: response.fcst.update_last,
+            forecasts: response.fcst.forecasts,
+        }
+    }
+}
+
+/// Forecasts of a model run as given in `fcst`, with the time the run was last updated
+#[derive(Debug)]
+pub struct RunForecasts<F> {
+    pub update_last: NaiveDateTime,
+    pub forecasts: Vec<F>,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct WgModel {
     pub id_model: IdModel,
@@ -51,7 +89,27 @@ pub struct WgModel {
     pub rundef: String,
 }
 
-#[derive(Debug, Clone)]
+/// Forecast for a single hour, variables are mapped from the windguru ones
+///
+/// | windguru  | field               | column                    | unit                     |
+/// |-----------|---------------------|---------------------------|--------------------------|
+/// | `GUST`    | `gust`              | `gust`                    | knots                    |
+/// | `WINDSPD` | `wind_speed`        | `wind_speed`              | knots                    |
+/// | `WINDDIR` | `wind_direction`    | `wind_direction`          | degrees, wind from       |
+/// | `TMPE`    | `temperature`       | `temperature`             | °C, at the spot altitude |
+/// | `TMP`     | `temperature_model` | `temperature_model`       | °C, at the model terrain |
+/// | `RH`      | `relative_humidity` | `relative_humidity`       | %                        |
+/// | `SLP`     | `slp`               | `sea_level_pressure`      | hPa                      |
+/// | `FLHGT`   | `flhgt`             | `freezing_level`          | m                        |
+/// | `SLHGT`   | `slhgt`             | `snow_line`               | m                        |
+/// | `TCDC`    | `tcdc`              | `cloud_cover_total`       | %                        |
+/// | `HCDC`    | `cloud_cover_high`  | `cloud_cover_high`        | %                        |
+/// | `MCDC`    | `cloud_cover_mid`   | `cloud_cover_mid`         | %                        |
+/// | `LCDC`    | `cloud_cover_low`   | `cloud_cover_low`         | %                        |
+/// | `APCP`    | `apcp`              | `precipitation_amount`    | mm over the model step   |
+/// | `APCP1`   | `apcp1`             | `precipitation_amount_1h` | mm over the last hour    |
+/// | `PCPT`    | `precipitation`     | `precipitation`           | precipitation type code  |
+#[derive(Debug, Clone, Serialize)]
 pub struct Fcst {
     pub gust: Option<f32>,
     pub flhgt: Option<i32>,
@@ -65,14 +123,15 @@ pub struct Fcst {
     pub slhgt: Option<i32>,
     pub precipitation: Option<i32>,
     pub temperature: Option<f32>,
-    pub forecast_for: NaiveDateTime, 
+    pub temperature_model: Option<f32>,
+    pub forecast_for: NaiveDateTime,
     pub forecast_from: NaiveDateTime,
     pub cloud_cover_high: Option<i32>,
     pub cloud_cover_mid: Option<i32>,
     pub cloud_cover_low: Option<i32>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Spot {
     #[serde(rename = "id_spot")]
     #[serde(deserialize_with = "deserialize_string_as_numeric")]
@@ -81,7 +140,15 @@ pub struct Spot {
     pub name: String,
     pub country: String,
     pub models: Vec<IdModel>,
-    pub gmt_hour_offset: i32
+    pub gmt_hour_offset: i32,
+    #[serde(default)]
+    pub lat: Option<f64>,
+    #[serde(default)]
+    pub lon: Option<f64>,
+    #[serde(default)]
+    pub alt: Option<i32>,
+    #[serde(default)]
+    pub tzid: Option<String>,
 }
 
 fn deserialize_string_as_numeric<'de, T: std::str::FromStr, D: de::Deserializer<'de>>(
@@ -110,76 +177,182 @@ impl TryFrom<HashMap<String, Spot>> for Spot {
     }
 }
 
-mod forecasts_arrays_format {
-    use crate::types::windguru::windguru_naivedatetime_format;
+pub(super) mod forecasts_arrays_format {
+    use serde_json::Value;
+
+    use crate::types::windguru::{columns::Columns, windguru_naivedatetime_format};
 
     use super::*;
 
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<Fcst>, D::Error>
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<RunForecasts<Fcst>, D::Error>
     where
         D: de::Deserializer<'de>,
     {
         let data_map: serde_json::Map<String, serde_json::Value> =
             serde_json::Map::deserialize(deserializer)?;
+        let columns: Columns<D::Error> = Columns::new(data_map, "hours")?;
 
-        let num_data_points = data_map["GUST"].as_array().unwrap().len();
-
-        let forecast_from = NaiveDateTime::from_timestamp_opt(data_map["initstamp"].as_i64().unwrap(), 0).unwrap();
-        let update_from = windguru_naivedatetime_format::deserialize(&data_map["update_last"])
-            .map_err(serde::de::Error::custom)?;
-
-        let mut data_vec = Vec::with_capacity(num_data_points);
-
-        for i in 0..num_data_points {
-            let gust = data_map["GUST"][i].as_f64().map(|val| val as f32);
-            let flhgt = data_map["FLHGT"][i].as_i64().map(|val| val as i32);
-            let slp = data_map["SLP"][i].as_i64().map(|val| val as i32);
-            let relative_humidity = data_map["RH"][i].as_i64().map(|val| val as i32);
-            let tcdc = data_map["TCDC"][i].as_i64().map(|val| val as i32);
-            let apcp = data_map["APCP1"][i].as_f64().map(|val| val as f32);
-            let apcp1 = data_map["APCP1"][i].as_f64().map(|val| val as f32);
-            let cloud_cover_high = data_map["HCDC"][i].as_i64().map(|val| val as i32);
-            let cloud_cover_mid = data_map["MCDC"][i].as_i64().map(|val| val as i32);
-            let cloud_cover_low = data_map["LCDC"][i].as_i64().map(|val| val as i32);
-            let wind_speed = data_map["WINDSPD"][i].as_f64().map(|val| val as f32);
-            let wind_direction = data_map["WINDDIR"][i].as_i64().map(|val| val as i32);
-            let slhgt = data_map["SLHGT"][i].as_i64().map(|val| val as i32);
-            let precipitation = data_map["PCPT"][i].as_i64().map(|val| val as i32);
-            let temperature = data_map["TMPE"][i].as_f64().map(|val| val as f32);
-            let forecast_for =
-                forecast_from + Duration::hours(data_map["hours"][i].as_i64().unwrap());
-
-            let data = Fcst {
-                gust,
-                flhgt,
-                slp,
-                relative_humidity,
-                tcdc,
-                apcp,
-                apcp1,
-                cloud_cover_high,
-                cloud_cover_mid,
-                cloud_cover_low,
-                wind_speed,
-                wind_direction,
-                slhgt,
-                precipitation,
-                temperature,
-                forecast_for,
-                forecast_from: update_from,
-            };
-
-            data_vec.push(data);
-        }
+        let (forecast_from, forecast_for, update_last) = forecast_times(&columns)?;
+        let gust = columns.f32s("GUST")?;
+        let flhgt = columns.i32s("FLHGT")?;
+        let slp = columns.i32s("SLP")?;
+        let relative_humidity = columns.i32s("RH")?;
+        let tcdc = columns.i32s("TCDC")?;
+        let apcp = columns.f32s("APCP")?;
+        let apcp1 = columns.f32s("APCP1")?;
+        let cloud_cover_high = columns.i32s("HCDC")?;
+        let cloud_cover_mid = columns.i32s("MCDC")?;
+        let cloud_cover_low = columns.i32s("LCDC")?;
+        let wind_speed = columns.f32s("WINDSPD")?;
+        let wind_direction = columns.i32s("WINDDIR")?;
+        let slhgt = columns.i32s("SLHGT")?;
+        let precipitation = columns.i32s("PCPT")?;
+        let temperature = columns.f32s("TMPE")?;
+        let temperature_model = columns.f32s("TMP")?;
+
+        let data_vec = (0..columns.len())
+            .map(|i| Fcst {
+                gust: gust[i],
+                flhgt: flhgt[i],
+                slp: slp[i],
+                relative_humidity: relative_humidity[i],
+                tcdc: tcdc[i],
+                apcp: apcp[i],
+                apcp1: apcp1[i],
+                cloud_cover_high: cloud_cover_high[i],
+                cloud_cover_mid: cloud_cover_mid[i],
+                cloud_cover_low: cloud_cover_low[i],
+                wind_speed: wind_speed[i],
+                wind_direction: wind_direction[i],
+                slhgt: slhgt[i],
+                precipitation: precipitation[i],
+                temperature: temperature[i],
+                temperature_model: temperature_model[i],
+                forecast_for: forecast_for[i],
+                forecast_from,
+            })
+            .collect();
+
+        Ok(RunForecasts {
+            update_last,
+            forecasts: data_vec,
+        })
+    }
+
+    /// Init time of the model run, hours the forecast is for and the time the run was
+    /// last updated
+    pub fn forecast_times<E: de::Error>(
+        columns: &Columns<E>,
+    ) -> Result<(NaiveDateTime, Vec<NaiveDateTime>, NaiveDateTime), E> {
+        let initstamp = columns.i64("initstamp")?;
+        let forecast_from = DateTime::from_timestamp(initstamp, 0)
+            .map(|init| init.naive_utc())
+            .ok_or_else(|| {
+                de::Error::custom(format!("initstamp: invalid timestamp {initstamp}"))
+            })?;
+        let update_last = windguru_naivedatetime_format::deserialize(columns.value("update_last")?)
+            .map_err(|err| de::Error::custom(format!("update_last: {err}")))?;
 
-        Ok(data_vec)
+        let forecast_for = columns
+            .required("hours", Value::as_i64, "an integer")?
+            .into_iter()
+            .map(|hours| forecast_from + Duration::hours(hours))
+            .collect();
+
+        Ok((forecast_from, forecast_for, update_last))
     }
 
     #[cfg(test)]
     mod tests {
+        use pretty_assertions::assert_eq;
+        use serde_json::json;
+
+        use super::*;
+
+        fn fcst() -> Value {
+            json!({
+                "initstamp": 1680955200,
+                "update_last": "2023-04-08 16:45:02",
+                "hours": [0, 1, 2],
+                "GUST": [9.4, 10, null],
+                "WINDSPD": [10, 11.9, 10.4],
+                "WINDDIR": [44, 42, 39],
+                "TMPE": [18.4, 18.3, 18.7],
+                "TMP": [17.9, 17.8, 18.2],
+                "APCP": [null, 0.4, 1.2],
+                "APCP1": [null, 0, 0.3],
+            })
+        }
+
+        fn deserialization_error(fcst: Value) -> String {
+            deserialize(fcst).unwrap_err().to_string()
+        }
 
         #[test]
-        fn test_deserialization() {}
+        fn test_deserialization() {
+            let run = deserialize(fcst()).unwrap();
+            let forecasts = run.forecasts;
+
+            assert_eq!(forecasts.len(), 3);
+            assert_eq!(forecasts[1].gust, Some(10.0));
+            assert_eq!(forecasts[2].gust, None);
+            assert_eq!(
+                (forecasts[1].temperature, forecasts[1].temperature_model),
+                (Some(18.3), Some(17.8))
+            );
+            assert_eq!(
+                (forecasts[2].apcp, forecasts[2].apcp1),
+                (Some(1.2), Some(0.3))
+            );
+            // variables missing from the response are stored as empty
+            assert!(forecasts.iter().all(|fcst| fcst.slp.is_none()));
+            // forecasts are issued at the init time of the run, not at its last update
+            let init = DateTime::from_timestamp(1680955200, 0).unwrap().naive_utc();
+            assert_eq!(forecasts[2].forecast_from, init);
+            assert_eq!(forecasts[2].forecast_for, init + Duration::hours(2));
+            assert_eq!(
+                run.update_last,
+                init + Duration::hours(4) + Duration::minutes(45) + Duration::seconds(2)
+            );
+
+            let mut missing_hours = fcst();
+            missing_hours.as_object_mut().unwrap().remove("hours");
+            assert_eq!(
+                deserialization_error(missing_hours),
+                "missing field `hours`"
+            );
+
+            let mut missing_initstamp = fcst();
+            missing_initstamp
+                .as_object_mut()
+                .unwrap()
+                .remove("initstamp");
+            assert_eq!(
+                deserialization_error(missing_initstamp),
+                "missing field `initstamp`"
+            );
+
+            let mut mistyped = fcst();
+            mistyped["WINDDIR"][1] = json!("NE");
+            assert_eq!(
+                deserialization_error(mistyped),
+                r#"WINDDIR[1]: invalid type: string "NE", expected a number"#
+            );
+
+            let mut shorter = fcst();
+            shorter["GUST"] = json!([9.4, 10]);
+            assert_eq!(
+                deserialization_error(shorter),
+                "GUST has 2 values, expected 3"
+            );
+
+            let mut not_array = fcst();
+            not_array["TMPE"] = json!(18.4);
+            assert_eq!(
+                deserialization_error(not_array),
+                "TMPE: invalid type: number, expected an array"
+            );
+        }
     }
 }
 
@@ -198,3 +371,39 @@ mod windguru_hour_minutes_format {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use pretty_assertions::assert_eq;
+
+    use super::Spot;
+
+    #[test]
+    fn deserialize_spot_metadata() {
+        let json_data = r#"{
+            "36048": {
+                "id_spot": "36048",
+                "spotname": "Canary Islands - Gran Canaria - Pozo / Vargas",
+                "country": "Spain",
+                "lat": 27.85,
+                "lon": -15.35,
+                "alt": 0,
+                "tzid": "Atlantic/Canary",
+                "gmt_hour_offset": 1,
+                "models": [100, 3]
+            }
+        }"#;
+
+        let spots: HashMap<String, Spot> = serde_json::from_str(json_data).unwrap();
+        let spot = Spot::try_from(spots).unwrap();
+
+        assert_eq!(spot.id, 36048);
+        assert_eq!(spot.models, vec![100, 3]);
+        assert_eq!(
+            (spot.lat, spot.lon, spot.alt),
+            (Some(27.85), Some(-15.35), Some(0))
+        );
+        assert_eq!(spot.tzid.as_deref(), Some("Atlantic/Canary"));
+    }
+}
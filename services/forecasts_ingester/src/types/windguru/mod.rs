@@ -1,5 +1,7 @@
+mod columns;
 pub mod forecast;
 pub mod station;
+pub mod wave;
 
 const FORMAT: &str = "%Y-%m-%d %H:%M:%S";
 
@@ -1,8 +1,19 @@
+use super::forecast::IdSpot;
 use super::FORMAT;
 use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
+use common::stations::default_avg_minutes;
 use serde::{Deserialize, Deserializer, Serialize};
 
-type IdStation = i32;
+pub type IdStation = i32;
+
+/// Station observing conditions at a spot, readings are fetched averaged over `avg_minutes`
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SpotStation {
+    pub id_spot: IdSpot,
+    pub id_station: IdStation,
+    #[serde(default = "default_avg_minutes")]
+    pub avg_minutes: u32,
+}
 
 #[derive(Serialize)]
 pub struct WindguruStationFetchParams {
@@ -21,14 +32,14 @@ impl Default for WindguruStationFetchParams {
             id_station: Default::default(),
             from: Default::default(),
             to: Default::default(),
-            avg_minutes: 5,
+            avg_minutes: default_avg_minutes(),
             method: "station_data".into(),
             graph_info: 1,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct WindguruStationReading {
     pub datetime_local: DateTime<FixedOffset>,
     pub gustiness: Option<f32>,
@@ -57,6 +68,10 @@ pub struct WindguruStationData {
 
     #[serde(rename = "endstamp", deserialize_with = "from_unixstamp")]
     pub datetime_end_utc: DateTime<Utc>,
+
+    /// Window the readings were requested to be averaged over, it is not part of the response
+    #[serde(skip, default = "default_avg_minutes")]
+    pub avg_minutes: u32,
 }
 
 fn from_unixstamp<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
@@ -74,8 +89,10 @@ where
 
 mod station_arrays_format {
     use chrono::NaiveDateTime;
+    use serde_json::Value;
 
     use super::*;
+    use crate::types::windguru::columns::Columns;
 
     pub fn deserialize<'de, D>(deserializer: D) -> Result<WindguruStationReadingsWithTime, D::Error>
     where
@@ -83,41 +100,47 @@ mod station_arrays_format {
     {
         let data_map: serde_json::Map<String, serde_json::Value> =
             serde_json::Map::deserialize(deserializer)?;
+        let columns: Columns<D::Error> = Columns::new(data_map, "datetime")?;
+
+        // offset is given in seconds east of UTC
+        let tzoffset = columns.i64("tzoffset")?;
+        let tzoffset = i32::try_from(tzoffset)
+            .ok()
+            .and_then(FixedOffset::east_opt)
+            .ok_or_else(|| {
+                serde::de::Error::custom(format!("tzoffset: invalid offset {tzoffset}"))
+            })?;
+
+        let datetime = columns.required("datetime", Value::as_str, "a datetime string")?;
+        let gustiness = columns.f32s("gustiness")?;
+        let temperature = columns.f32s("temperature")?;
+        let wind_avg = columns.f32s("wind_avg")?;
+        let wind_max = columns.f32s("wind_max")?;
+        let wind_min = columns.f32s("wind_min")?;
+        let wind_direction = columns.i32s("wind_direction")?;
+        let relative_humidity = columns.i32s("rh")?;
+        let mean_sea_level_pressure = columns.f32s("mslp")?;
 
-        let num_data_points = data_map["datetime"].as_array().unwrap().len();
-
-        let mut readings = Vec::new();
-
-        let tzoffset =
-            FixedOffset::west_opt(data_map["tzoffset"].as_i64().map(|val| val as i32).unwrap())
-                .unwrap();
-        for i in 0..num_data_points {
-            let datetime_local: DateTime<FixedOffset> = DateTime::from_local(
-                NaiveDateTime::parse_from_str(data_map["datetime"][i].as_str().unwrap(), FORMAT)
-                    .map_err(serde::de::Error::custom)?,
-                tzoffset,
-            );
-            let gustiness = data_map["gustiness"][i].as_f64().map(|val| val as f32);
-            let temperature = data_map["temperature"][i].as_f64().map(|val| val as f32);
-            let wind_avg = data_map["wind_avg"][i].as_f64().map(|val| val as f32);
-            let wind_max = data_map["wind_max"][i].as_f64().map(|val| val as f32);
-            let wind_min = data_map["wind_min"][i].as_f64().map(|val| val as f32);
-            let wind_direction = data_map["wind_direction"][i].as_i64().map(|val| val as i32);
-            let relative_humidity = data_map["rh"][i].as_i64().map(|val| val as i32);
-            let mean_sea_level_pressure = data_map["mslp"][i].as_f64().map(|val| val as f32);
-
-            readings.push(WindguruStationReading {
-                datetime_local,
-                gustiness,
-                temperature,
-                wind_avg,
-                wind_max,
-                wind_min,
-                wind_direction,
-                relative_humidity,
-                mean_sea_level_pressure,
+        let readings = datetime
+            .into_iter()
+            .enumerate()
+            .map(|(i, datetime)| {
+                let datetime = NaiveDateTime::parse_from_str(datetime, FORMAT)
+                    .map_err(|err| serde::de::Error::custom(format!("datetime[{i}]: {err}")))?;
+
+                Ok(WindguruStationReading {
+                    datetime_local: DateTime::from_local(datetime, tzoffset),
+                    gustiness: gustiness[i],
+                    temperature: temperature[i],
+                    wind_avg: wind_avg[i],
+                    wind_max: wind_max[i],
+                    wind_min: wind_min[i],
+                    wind_direction: wind_direction[i],
+                    relative_humidity: relative_humidity[i],
+                    mean_sea_level_pressure: mean_sea_level_pressure[i],
+                })
             })
-        }
+            .collect::<Result<_, D::Error>>()?;
 
         Ok(WindguruStationReadingsWithTime { tzoffset, readings })
     }
@@ -125,6 +148,8 @@ mod station_arrays_format {
 
 #[cfg(test)]
 mod tests {
+    use serde_json::json;
+
     use super::WindguruStationData;
 
     #[test]
@@ -134,12 +159,77 @@ mod tests {
 
         let result: Result<WindguruStationData, _> = serde_path_to_error::deserialize(jd);
 
-        let _data = match result {
+        let data = match result {
             Ok(data) => data,
             Err(err) => {
                 panic!("err_for_key={} details={}", err.path(), err)
             }
         };
+
+        // 2023-04-15 07:30:00 at UTC+3
+        assert_eq!(
+            data.readings.readings[0].datetime_local.timestamp(),
+            1681533000
+        );
+    }
+
+    #[test]
+    fn reject_malformed_windsurfing_data() {
+        let proper: serde_json::Value =
+            serde_json::from_str(create_test_proper_windguru_station_data()).unwrap();
+        let deserialization_error = |data: serde_json::Value| {
+            serde_json::from_value::<WindguruStationData>(data)
+                .unwrap_err()
+                .to_string()
+        };
+
+        let mut missing_datetime = proper.clone();
+        missing_datetime.as_object_mut().unwrap().remove("datetime");
+        assert_eq!(
+            deserialization_error(missing_datetime),
+            "missing field `datetime`"
+        );
+
+        let mut missing_tzoffset = proper.clone();
+        missing_tzoffset.as_object_mut().unwrap().remove("tzoffset");
+        assert_eq!(
+            deserialization_error(missing_tzoffset),
+            "missing field `tzoffset`"
+        );
+
+        let mut invalid_datetime = proper.clone();
+        invalid_datetime["datetime"][1] = json!("15.04.2023 07:36");
+        assert_eq!(
+            deserialization_error(invalid_datetime),
+            "datetime[1]: input contains invalid characters"
+        );
+
+        let mut mistyped = proper.clone();
+        mistyped["rh"][0] = json!(true);
+        assert_eq!(
+            deserialization_error(mistyped),
+            "rh[0]: invalid type: boolean `true`, expected a number"
+        );
+
+        let mut longer = proper.clone();
+        longer["wind_avg"] = json!([23.7, 22.2, 20.1]);
+        assert_eq!(
+            deserialization_error(longer),
+            "wind_avg has 3 values, expected 2"
+        );
+
+        // variables absent from the response are read as missing values
+        let mut without_gustiness = proper;
+        without_gustiness
+            .as_object_mut()
+            .unwrap()
+            .remove("gustiness");
+        let data: WindguruStationData = serde_json::from_value(without_gustiness).unwrap();
+        assert!(data
+            .readings
+            .readings
+            .iter()
+            .all(|reading| reading.gustiness.is_none()));
     }
 
     fn create_test_proper_windguru_station_data() -> &'static str {
@@ -0,0 +1,171 @@
+use chrono::NaiveDateTime;
+use serde::{de, Deserialize, Serialize};
+
+use super::forecast::{IdSpot, RunForecasts, WgModel};
+
+/// Forecast of a wave model, such models are offered as `id_model_wave` of a spot
+#[derive(Deserialize, Debug)]
+#[serde(from = "WindguruWaveForecastsResponse")]
+pub struct WindguruWaveForecasts {
+    pub id_spot: IdSpot,
+    pub wgmodel: WgModel,
+    /// Time windguru last updated the run
+    pub update_last: NaiveDateTime,
+    pub forecasts: Vec<WaveFcst>,
+}
+
+#[derive(Deserialize)]
+struct WindguruWaveForecastsResponse {
+    id_spot: IdSpot,
+    wgmodel: WgModel,
+    #[serde(with = "wave_arrays_format")]
+    fcst: RunForecasts<WaveFcst>,
+}
+
+impl From<WindguruWaveForecastsResponse> for WindguruWaveForecasts {
+    fn from(response: WindguruWaveForecastsResponse) -> Self {
+        Self {
+            id_spot: response.id_spot,
+            wgmodel: response.wgmodel,
+            update_last: response.fcst.update_last,
+            forecasts: response.fcst.forecasts,
+        }
+    }
+}
+
+/// Wave forecast for a single hour, variables are mapped from the windguru ones
+///
+/// | windguru | field                   | unit                |
+/// |----------|-------------------------|---------------------|
+/// | `HTSGW`  | `wave_height`           | m, significant      |
+/// | `PERPW`  | `wave_period`           | s, peak             |
+/// | `DIRPW`  | `wave_direction`        | degrees, waves from |
+/// | `WVHGT`  | `wind_wave_height`      | m                   |
+/// | `WVPER`  | `wind_wave_period`      | s                   |
+/// | `WVDIR`  | `wind_wave_direction`   | degrees, waves from |
+/// | `SWELL1` | `swell1_height`         | m                   |
+/// | `SWPER1` | `swell1_period`         | s                   |
+/// | `SWDIR1` | `swell1_direction`      | degrees, waves from |
+/// | `SWELL2` | `swell2_height`         | m                   |
+/// | `SWPER2` | `swell2_period`         | s                   |
+/// | `SWDIR2` | `swell2_direction`      | degrees, waves from |
+#[derive(Debug, Clone, Serialize)]
+pub struct WaveFcst {
+    pub wave_height: Option<f32>,
+    pub wave_period: Option<f32>,
+    pub wave_direction: Option<i32>,
+    pub wind_wave_height: Option<f32>,
+    pub wind_wave_period: Option<f32>,
+    pub wind_wave_direction: Option<i32>,
+    pub swell1_height: Option<f32>,
+    pub swell1_period: Option<f32>,
+    pub swell1_direction: Option<i32>,
+    pub swell2_height: Option<f32>,
+    pub swell2_period: Option<f32>,
+    pub swell2_direction: Option<i32>,
+    pub forecast_for: NaiveDateTime,
+    pub forecast_from: NaiveDateTime,
+}
+
+mod wave_arrays_format {
+    use crate::types::windguru::{columns::Columns, forecast::forecasts_arrays_format};
+
+    use super::*;
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<RunForecasts<WaveFcst>, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let data_map: serde_json::Map<String, serde_json::Value> =
+            serde_json::Map::deserialize(deserializer)?;
+        let columns: Columns<D::Error> = Columns::new(data_map, "hours")?;
+
+        let (forecast_from, forecast_for, update_last) =
+            forecasts_arrays_format::forecast_times(&columns)?;
+        let wave_height = columns.f32s("HTSGW")?;
+        let wave_period = columns.f32s("PERPW")?;
+        let wave_direction = columns.i32s("DIRPW")?;
+        let wind_wave_height = columns.f32s("WVHGT")?;
+        let wind_wave_period = columns.f32s("WVPER")?;
+        let wind_wave_direction = columns.i32s("WVDIR")?;
+        let swell1_height = columns.f32s("SWELL1")?;
+        let swell1_period = columns.f32s("SWPER1")?;
+        let swell1_direction = columns.i32s("SWDIR1")?;
+        let swell2_height = columns.f32s("SWELL2")?;
+        let swell2_period = columns.f32s("SWPER2")?;
+        let swell2_direction = columns.i32s("SWDIR2")?;
+
+        let data_vec = (0..columns.len())
+            .map(|i| WaveFcst {
+                wave_height: wave_height[i],
+                wave_period: wave_period[i],
+                wave_direction: wave_direction[i],
+                wind_wave_height: wind_wave_height[i],
+                wind_wave_period: wind_wave_period[i],
+                wind_wave_direction: wind_wave_direction[i],
+                swell1_height: swell1_height[i],
+                swell1_period: swell1_period[i],
+                swell1_direction: swell1_direction[i],
+                swell2_height: swell2_height[i],
+                swell2_period: swell2_period[i],
+                swell2_direction: swell2_direction[i],
+                forecast_for: forecast_for[i],
+                forecast_from,
+            })
+            .collect();
+
+        Ok(RunForecasts {
+            update_last,
+            forecasts: data_vec,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::WindguruWaveForecasts;
+
+    #[test]
+    fn deserialize_wave_forecast() {
+        let json_data = r#"{
+            "id_spot": 36048,
+            "wgmodel": {
+                "id_model": 84,
+                "model": "gfswave",
+                "model_name": "GFS Wave 16 km",
+                "initdate": "2023-04-08 12:00:00",
+                "hr_start": 0,
+                "hr_end": 384,
+                "hr_step": 3,
+                "wave": true,
+                "rundef": "2023040812x0x240x0x240-2023040800x243x384x255x384"
+            },
+            "fcst": {
+                "initstamp": 1680955200,
+                "update_last": "2023-04-08 17:05:11",
+                "hours": [0, 3],
+                "HTSGW": [1.9, 2.1],
+                "PERPW": [8.6, 8.8],
+                "DIRPW": [24, 26],
+                "SWELL1": [1.6, null],
+                "SWPER1": [8.7, null],
+                "SWDIR1": [21, null]
+            }
+        }"#;
+
+        let jd = &mut serde_json::Deserializer::from_str(json_data);
+        let forecast: WindguruWaveForecasts = serde_path_to_error::deserialize(jd).unwrap();
+
+        assert!(forecast.wgmodel.wave);
+        assert_eq!(forecast.forecasts.len(), 2);
+        assert_eq!(forecast.forecasts[1].wave_height, Some(2.1));
+        assert_eq!(forecast.forecasts[1].swell1_height, None);
+        assert_eq!(forecast.forecasts[0].swell2_height, None);
+        assert_eq!(
+            forecast.forecasts[1].forecast_for - forecast.forecasts[0].forecast_for,
+            chrono::Duration::hours(3)
+        );
+    }
+}
@@ -0,0 +1,239 @@
+use std::{collections::BTreeSet, time::Duration};
+
+use common::watch_list::list_watched_spots;
+use reqwest::Client;
+use sqlx::{postgres::PgPoolOptions, PgPool};
+
+use crate::{
+    config::WatchListConfig,
+    types::windguru::{
+        forecast::{IdModel, WindguruConfig},
+        station::SpotStation,
+    },
+};
+
+pub use common::watch_list::{WatchedSpot, WatchedStation};
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WatchList {
+    pub spots: Vec<WatchedSpot>,
+}
+
+impl WatchList {
+    /// Every configured spot is watched with all configured models
+    pub fn from_config(config: &WindguruConfig) -> Self {
+        let spots = config
+            .spots
+            .iter()
+            .map(|id_spot| WatchedSpot {
+                id_spot: *id_spot,
+                models: config.models.clone(),
+                stations: config
+                    .stations
+                    .iter()
+                    .filter(|station| station.id_spot == *id_spot)
+                    .map(|station| WatchedStation {
+                        id_station: station.id_station,
+                        avg_minutes: station.avg_minutes,
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Self { spots }
+    }
+
+    /// Models selected for any of the spots
+    pub fn models(&self) -> Vec<IdModel> {
+        self.spots
+            .iter()
+            .flat_map(|spot| spot.models.iter().copied())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    pub fn spot_stations(&self) -> Vec<SpotStation> {
+        self.spots
+            .iter()
+            .flat_map(|spot| {
+                spot.stations.iter().map(|station| SpotStation {
+                    id_spot: spot.id_spot,
+                    id_station: station.id_station,
+                    avg_minutes: station.avg_minutes,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Watch list along with where it came from
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadedWatchList {
+    /// Loaded from watcher-settings, or the static config when no source is configured
+    Source(WatchList),
+    /// Static config used while the configured source is unreachable, it may lag behind
+    /// the source, so it is never persisted
+    Fallback(WatchList),
+}
+
+impl LoadedWatchList {
+    pub fn is_fallback(&self) -> bool {
+        matches!(self, Self::Fallback(_))
+    }
+
+    pub fn into_inner(self) -> WatchList {
+        match self {
+            Self::Source(watch_list) | Self::Fallback(watch_list) => watch_list,
+        }
+    }
+}
+
+enum WatchListSource {
+    Api { client: Client, url: String },
+    Postgresql(PgPool),
+}
+
+/// Loads the watch list from watcher-settings, falling back to the static config
+pub struct WatchListLoader {
+    source: Option<WatchListSource>,
+    fallback: WatchList,
+}
+
+impl WatchListLoader {
+    pub fn new(config: Option<&WatchListConfig>, fallback: WatchList) -> Self {
+        let source = config.and_then(|config| match config {
+            WatchListConfig::Api(config) => Some(WatchListSource::Api {
+                client: Client::builder()
+                    .timeout(Duration::from_secs(10))
+                    .build()
+                    .unwrap(),
+                url: config.url.trim_end_matches('/').to_owned(),
+            }),
+            WatchListConfig::Postgresql(config) => {
+                match PgPoolOptions::new()
+                    .acquire_timeout(Duration::from_secs(10))
+                    .connect_lazy(&config.connection_url)
+                {
+                    Ok(pool) => Some(WatchListSource::Postgresql(pool)),
+                    Err(err) => {
+                        tracing::error!("invalid watch list database url {}", err);
+                        None
+                    }
+                }
+            }
+        });
+
+        Self { source, fallback }
+    }
+
+    pub async fn load(&self) -> LoadedWatchList {
+        let Some(source) = &self.source else {
+            return LoadedWatchList::Source(self.fallback.clone());
+        };
+
+        let result = match source {
+            WatchListSource::Api { client, url } => load_from_api(client, url).await,
+            WatchListSource::Postgresql(pool) => load_from_db(pool).await,
+        };
+
+        match result {
+            Ok(watch_list) => {
+                tracing::debug!(spots = watch_list.spots.len(), "loaded watch list");
+                LoadedWatchList::Source(watch_list)
+            }
+            Err(err) => {
+                tracing::warn!("unable to load watch list, using static config {}", err);
+                LoadedWatchList::Fallback(self.fallback.clone())
+            }
+        }
+    }
+}
+
+async fn load_from_api(client: &Client, url: &str) -> anyhow::Result<WatchList> {
+    let spots = client
+        .get(format!("{url}/spots"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    Ok(WatchList { spots })
+}
+
+async fn load_from_db(pool: &PgPool) -> anyhow::Result<WatchList> {
+    let spots = list_watched_spots(pool).await?;
+
+    Ok(WatchList { spots })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::config::WatchListApiConfig;
+
+    #[test]
+    fn builds_watch_list_from_static_config() {
+        let config = WindguruConfig {
+            url: "http://localhost:8001".into(),
+            spots: vec![36048, 500],
+            models: vec![3],
+            stations: vec![SpotStation {
+                id_spot: 36048,
+                id_station: 2764,
+                avg_minutes: 5,
+            }],
+            rate_limit: Default::default(),
+        };
+
+        let watch_list = WatchList::from_config(&config);
+
+        assert_eq!(
+            watch_list.spots,
+            vec![
+                WatchedSpot {
+                    id_spot: 36048,
+                    models: vec![3],
+                    stations: vec![WatchedStation {
+                        id_station: 2764,
+                        avg_minutes: 5
+                    }],
+                },
+                WatchedSpot {
+                    id_spot: 500,
+                    models: vec![3],
+                    stations: vec![],
+                },
+            ]
+        );
+        assert_eq!(watch_list.spot_stations(), config.stations);
+    }
+
+    #[tokio::test]
+    async fn marks_static_config_used_for_unreachable_source() {
+        let fallback = WatchList {
+            spots: vec![WatchedSpot {
+                id_spot: 36048,
+                models: vec![3],
+                stations: vec![],
+            }],
+        };
+        let unreachable = WatchListConfig::Api(WatchListApiConfig {
+            url: "http://127.0.0.1:1".into(),
+        });
+
+        assert_eq!(
+            WatchListLoader::new(None, fallback.clone()).load().await,
+            LoadedWatchList::Source(fallback.clone())
+        );
+        assert_eq!(
+            WatchListLoader::new(Some(&unreachable), fallback.clone())
+                .load()
+                .await,
+            LoadedWatchList::Fallback(fallback)
+        );
+    }
+}
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS watched_spot_stations (
+  id_spot INT NOT NULL,
+  id_station INT NOT NULL,
+  PRIMARY KEY (id_spot, id_station)
+);
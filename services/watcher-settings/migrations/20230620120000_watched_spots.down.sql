@@ -0,0 +1,4 @@
+ALTER TABLE watched_spot_stations DROP CONSTRAINT IF EXISTS fk_watched_spot;
+ALTER TABLE watched_spot_stations DROP COLUMN IF EXISTS avg_minutes;
+
+DROP TABLE IF EXISTS watched_spots;
@@ -0,0 +1,16 @@
+-- spots the ingester fetches forecasts for, an empty models list means every offered model
+CREATE TABLE IF NOT EXISTS watched_spots (
+  id_spot INT PRIMARY KEY,
+  models INTEGER[] NOT NULL DEFAULT '{}',
+  created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO watched_spots (id_spot) SELECT DISTINCT id_spot FROM watched_spot_stations ON CONFLICT DO NOTHING;
+
+ALTER TABLE watched_spot_stations ADD COLUMN IF NOT EXISTS avg_minutes INT NOT NULL DEFAULT 5;
+ALTER TABLE watched_spot_stations
+  ADD CONSTRAINT fk_watched_spot
+    FOREIGN KEY (id_spot)
+      REFERENCES watched_spots (id_spot)
+      ON DELETE CASCADE;
@@ -0,0 +1,50 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ApiError {
+    #[error("{0}")]
+    Validation(String),
+
+    #[error("{0} not found")]
+    NotFound(String),
+
+    #[error("{0} already exists")]
+    Conflict(String),
+
+    #[error("database error err={0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            ApiError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Database(err) => {
+                tracing::error!("request failed {}", err);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+
+        let body = ErrorBody {
+            error: match status {
+                StatusCode::INTERNAL_SERVER_ERROR => "internal server error".into(),
+                _ => self.to_string(),
+            },
+        };
+
+        (status, Json(body)).into_response()
+    }
+}
@@ -49,9 +49,36 @@ pub struct PostgresqlConfig {
     pub connection_url: String,
 }
 
+#[derive(Deserialize, Debug)]
+pub struct ServerConfig {
+    #[serde(default = "default_host")]
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+}
+
+fn default_host() -> String {
+    "0.0.0.0".into()
+}
+
+fn default_port() -> u16 {
+    8080
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: default_host(),
+            port: default_port(),
+        }
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Settings {
     pub storage: DataStorage,
+    #[serde(default)]
+    pub server: ServerConfig,
 }
 
 impl Display for Settings {
@@ -1,2 +1,5 @@
+pub mod api;
 pub mod config;
 pub mod logging;
+pub mod repository;
+pub mod spots;
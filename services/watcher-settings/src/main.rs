@@ -1,3 +1,69 @@
-fn main() {
-    println!("Hello, world!");
+use std::net::SocketAddr;
+use std::process::ExitCode;
+
+use lib::api::router;
+use lib::config::{init_config, DataStorage};
+use lib::logging::init_logger;
+use sqlx::{Pool, Postgres};
+use tokio::signal::unix::{signal, SignalKind};
+
+/// Containers are stopped with SIGTERM, SIGINT stops the service run from a terminal
+async fn shutdown_signal() {
+    let mut sigterm = signal(SignalKind::terminate()).expect("unable to listen for SIGTERM");
+
+    tokio::select! {
+        _ = sigterm.recv() => tracing::info!("received SIGTERM, shutting down"),
+        _ = tokio::signal::ctrl_c() => tracing::info!("received SIGINT, shutting down"),
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    init_logger();
+    let settings = init_config();
+    tracing::info!("Starting watcher-settings with settings: {}", settings);
+
+    let pool = match &settings.storage {
+        DataStorage::Postgresql(config) => {
+            match Pool::<Postgres>::connect(&config.connection_url).await {
+                Ok(pool) => pool,
+                Err(err) => {
+                    tracing::error!("Unable to connect to postgresql: {err}");
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        DataStorage::S3(_) => {
+            tracing::error!("Watched spots can be stored only in postgresql storage");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let addr: SocketAddr =
+        match format!("{}:{}", settings.server.host, settings.server.port).parse() {
+            Ok(addr) => addr,
+            Err(err) => {
+                tracing::error!("Invalid server address: {err}");
+                return ExitCode::FAILURE;
+            }
+        };
+    tracing::info!("listening on {}", addr);
+
+    let server = match axum::Server::try_bind(&addr) {
+        Ok(server) => server,
+        Err(err) => {
+            tracing::error!("Unable to listen on {addr}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(err) = server
+        .serve(router(pool).into_make_service())
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+    {
+        tracing::error!("Server failed: {err}");
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
 }
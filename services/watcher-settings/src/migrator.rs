@@ -1,10 +1,43 @@
-use std::path::Path;
+use std::path::PathBuf;
+use std::process::ExitCode;
 
+use clap::{Parser, Subcommand};
+use common::migrations::{migrations_script, MigrationError, Migrations};
 use lib::config::ConfigCache;
 use lib::config::DataStorage;
 use lib::logging::init_logger;
 use serde::Deserialize;
-use sqlx::{migrate::Migrator, Pool, Postgres};
+use sqlx::{Pool, Postgres};
+
+#[derive(Parser)]
+struct Cli {
+    /// Directory with the migrations of the service
+    #[arg(long, default_value = "./migrations")]
+    migrations: PathBuf,
+    /// Runs pending migrations when omitted
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List migrations of the directory, applied and pending
+    Status,
+    /// Apply pending migrations
+    Run {
+        /// Print SQL of the pending migrations without applying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Revert applied migrations newer than a version, 0 reverts all of them
+    Revert {
+        #[arg(long)]
+        to: i64,
+        /// Print SQL of the down migrations without applying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
 
 #[derive(Deserialize)]
 struct Config {
@@ -15,24 +48,56 @@ fn init_config() -> Config {
     ConfigCache::default().into::<Config>()
 }
 
+async fn migrate(connection_url: &str, cli: Cli) -> Result<(), MigrationError> {
+    let migrations = Migrations::new(&cli.migrations).await?;
+    let pool = Pool::<Postgres>::connect(connection_url).await?;
+
+    match cli.command.unwrap_or(Command::Run { dry_run: false }) {
+        Command::Status => {
+            for status in migrations.status(&pool).await? {
+                println!("{status}");
+            }
+        }
+        Command::Run { dry_run: true } => {
+            println!("{}", migrations_script(&migrations.pending(&pool).await?));
+        }
+        Command::Run { dry_run: false } => {
+            migrations.run(&pool).await?;
+            tracing::info!("Storage schema migration was successfull");
+        }
+        Command::Revert { to, dry_run: true } => {
+            println!(
+                "{}",
+                migrations_script(&migrations.reverted(&pool, to).await?)
+            );
+        }
+        Command::Revert { to, dry_run: false } => {
+            migrations.revert(&pool, to).await?;
+            tracing::info!(to, "Storage schema was reverted");
+        }
+    }
+
+    Ok(())
+}
+
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     init_logger();
+    let cli = Cli::parse();
     let config = init_config();
 
     match config.storage {
-        DataStorage::S3(_config) => unimplemented!(),
+        DataStorage::S3(_config) => {
+            tracing::error!("Schema migrations are supported only for postgresql storage");
+            return ExitCode::FAILURE;
+        }
         DataStorage::Postgresql(config) => {
-            let migrations_path = Path::new("./migrations");
-            let migrator = Migrator::new(migrations_path)
-                .await
-                .expect("unable to creat migrator}");
-
-            let pool = Pool::<Postgres>::connect(&config.connection_url)
-                .await
-                .unwrap();
-            migrator.run(&pool).await.unwrap();
-            tracing::info!("Storage schema migration was successfull");
+            if let Err(err) = migrate(&config.connection_url, cli).await {
+                tracing::error!("Storage schema migration failed: {err}");
+                return ExitCode::FAILURE;
+            }
         }
     }
+
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,172 @@
+use sqlx::{PgPool, Postgres, QueryBuilder, Transaction};
+
+use crate::spots::{IdModel, IdSpot, IdStation, WatchedSpot, WatchedStation};
+
+pub use common::watch_list::list_watched_spots;
+
+pub async fn get_spot(pool: &PgPool, id_spot: IdSpot) -> Result<Option<WatchedSpot>, sqlx::Error> {
+    let models: Option<(Vec<IdModel>,)> =
+        sqlx::query_as("SELECT models FROM watched_spots WHERE id_spot = $1")
+            .bind(id_spot)
+            .fetch_optional(pool)
+            .await?;
+    let Some((models,)) = models else {
+        return Ok(None);
+    };
+
+    Ok(Some(WatchedSpot {
+        id_spot,
+        models,
+        stations: list_stations(pool, id_spot).await?,
+    }))
+}
+
+pub async fn list_stations(
+    pool: &PgPool,
+    id_spot: IdSpot,
+) -> Result<Vec<WatchedStation>, sqlx::Error> {
+    let stations: Vec<(IdStation, i32)> = sqlx::query_as(
+        "SELECT id_station, avg_minutes FROM watched_spot_stations WHERE id_spot = $1 ORDER BY id_station",
+    )
+    .bind(id_spot)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(stations
+        .into_iter()
+        .map(|(id_station, avg_minutes)| WatchedStation {
+            id_station,
+            avg_minutes: avg_minutes as u32,
+        })
+        .collect())
+}
+
+/// Returns false when the spot is already watched
+pub async fn create_spot(pool: &PgPool, spot: &WatchedSpot) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let inserted = sqlx::query(
+        "INSERT INTO watched_spots (id_spot, models) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+    )
+    .bind(spot.id_spot)
+    .bind(&spot.models)
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
+    if inserted == 0 {
+        return Ok(false);
+    }
+
+    insert_stations(&mut tx, spot.id_spot, &spot.stations).await?;
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+/// Replaces models and stations of a spot, returns false when the spot is not watched
+pub async fn update_spot(pool: &PgPool, spot: &WatchedSpot) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let updated =
+        sqlx::query("UPDATE watched_spots SET models = $2, updated_at = NOW() WHERE id_spot = $1")
+            .bind(spot.id_spot)
+            .bind(&spot.models)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+    if updated == 0 {
+        return Ok(false);
+    }
+
+    sqlx::query("DELETE FROM watched_spot_stations WHERE id_spot = $1")
+        .bind(spot.id_spot)
+        .execute(&mut tx)
+        .await?;
+    insert_stations(&mut tx, spot.id_spot, &spot.stations).await?;
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+/// Stations of the spot are removed together with it
+pub async fn delete_spot(pool: &PgPool, id_spot: IdSpot) -> Result<bool, sqlx::Error> {
+    let deleted = sqlx::query("DELETE FROM watched_spots WHERE id_spot = $1")
+        .bind(id_spot)
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+    Ok(deleted > 0)
+}
+
+/// Returns false when the spot is not watched
+pub async fn upsert_station(
+    pool: &PgPool,
+    id_spot: IdSpot,
+    station: &WatchedStation,
+) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let exists: Option<(IdSpot,)> =
+        sqlx::query_as("SELECT id_spot FROM watched_spots WHERE id_spot = $1 FOR UPDATE")
+            .bind(id_spot)
+            .fetch_optional(&mut tx)
+            .await?;
+    if exists.is_none() {
+        return Ok(false);
+    }
+
+    sqlx::query(
+        r#"INSERT INTO watched_spot_stations (id_spot, id_station, avg_minutes) VALUES ($1, $2, $3)
+        ON CONFLICT (id_spot, id_station) DO UPDATE SET avg_minutes = EXCLUDED.avg_minutes"#,
+    )
+    .bind(id_spot)
+    .bind(station.id_station)
+    .bind(station.avg_minutes as i32)
+    .execute(&mut tx)
+    .await?;
+    sqlx::query("UPDATE watched_spots SET updated_at = NOW() WHERE id_spot = $1")
+        .bind(id_spot)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+pub async fn delete_station(
+    pool: &PgPool,
+    id_spot: IdSpot,
+    id_station: IdStation,
+) -> Result<bool, sqlx::Error> {
+    let deleted =
+        sqlx::query("DELETE FROM watched_spot_stations WHERE id_spot = $1 AND id_station = $2")
+            .bind(id_spot)
+            .bind(id_station)
+            .execute(pool)
+            .await?
+            .rows_affected();
+
+    Ok(deleted > 0)
+}
+
+async fn insert_stations(
+    tx: &mut Transaction<'_, Postgres>,
+    id_spot: IdSpot,
+    stations: &[WatchedStation],
+) -> Result<(), sqlx::Error> {
+    if stations.is_empty() {
+        return Ok(());
+    }
+
+    let mut query_builder =
+        QueryBuilder::new("INSERT INTO watched_spot_stations (id_spot, id_station, avg_minutes) ");
+    query_builder.push_values(stations, |mut b, station| {
+        b.push_bind(id_spot)
+            .push_bind(station.id_station)
+            .push_bind(station.avg_minutes as i32);
+    });
+    query_builder.build().execute(&mut *tx).await?;
+
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use common::stations::default_avg_minutes;
+use serde::Deserialize;
+
+pub use common::watch_list::{IdModel, IdSpot, IdStation, WatchedSpot, WatchedStation};
+
+/// Averaging windows of station readings offered by Windguru
+pub const AVG_MINUTES: [u32; 4] = [1, 5, 10, 60];
+
+/// Body of a request replacing settings of an already watched spot
+#[derive(Deserialize, Debug, Clone)]
+pub struct WatchedSpotUpdate {
+    #[serde(default)]
+    pub models: Vec<IdModel>,
+    #[serde(default)]
+    pub stations: Vec<WatchedStation>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct StationUpdate {
+    #[serde(default = "default_avg_minutes")]
+    pub avg_minutes: u32,
+}
+
+impl WatchedSpotUpdate {
+    pub fn into_spot(self, id_spot: IdSpot) -> WatchedSpot {
+        WatchedSpot {
+            id_spot,
+            models: self.models,
+            stations: self.stations,
+        }
+    }
+}
+
+pub fn validate_id(kind: &str, id: i32) -> Result<(), String> {
+    if id <= 0 {
+        return Err(format!("{kind} id has to be positive, got {id}"));
+    }
+    Ok(())
+}
+
+pub fn validate_station(station: &WatchedStation) -> Result<(), String> {
+    validate_id("station", station.id_station)?;
+
+    if !AVG_MINUTES.contains(&station.avg_minutes) {
+        return Err(format!(
+            "avg_minutes of station {} has to be one of {:?}, got {}",
+            station.id_station, AVG_MINUTES, station.avg_minutes
+        ));
+    }
+    Ok(())
+}
+
+pub fn validate_spot(spot: &WatchedSpot) -> Result<(), String> {
+    validate_id("spot", spot.id_spot)?;
+
+    for (i, model) in spot.models.iter().enumerate() {
+        validate_id("model", *model)?;
+        if spot.models[..i].contains(model) {
+            return Err(format!("model {model} is listed more than once"));
+        }
+    }
+
+    for (i, station) in spot.stations.iter().enumerate() {
+        validate_station(station)?;
+        if spot.stations[..i]
+            .iter()
+            .any(|other| other.id_station == station.id_station)
+        {
+            return Err(format!(
+                "station {} is listed more than once",
+                station.id_station
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(
+        id_spot: IdSpot,
+        models: Vec<IdModel>,
+        stations: Vec<WatchedStation>,
+    ) -> Result<(), String> {
+        validate_spot(&WatchedSpot {
+            id_spot,
+            models,
+            stations,
+        })
+    }
+
+    #[test]
+    fn validates_watched_spot() {
+        let station = WatchedStation {
+            id_station: 2764,
+            avg_minutes: 5,
+        };
+
+        assert_eq!(validate(36048, vec![3, 45], vec![station.clone()]), Ok(()));
+        assert!(validate(0, vec![], vec![]).is_err());
+        assert!(validate(36048, vec![3, 3], vec![]).is_err());
+        assert!(validate(36048, vec![], vec![station.clone(), station]).is_err());
+        assert!(validate(
+            36048,
+            vec![],
+            vec![WatchedStation {
+                id_station: 2764,
+                avg_minutes: 7
+            }]
+        )
+        .is_err());
+    }
+}